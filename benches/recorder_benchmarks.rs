@@ -1,10 +1,11 @@
-use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 use rapid_recorder::latest_reading_holder::LatestReadingHolder;
 use rapid_recorder::prelude::*;
+use rayon::prelude::*;
 use strum_macros::EnumIter;
 
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 enum BenchReadings {
     Reading0,
     Reading1,
@@ -12,6 +13,23 @@ enum BenchReadings {
 }
 impl_rapid_recorder_named_usize!(BenchReadings);
 
+#[repr(u32)]
+#[derive(EnumIter, Debug)]
+enum ScalingReadings {
+    Thread0,
+    Thread1,
+    Thread2,
+    Thread3,
+    Thread4,
+    Thread5,
+    Thread6,
+    Thread7,
+}
+impl_rapid_recorder_named_usize!(ScalingReadings);
+
+const SCALING_THREAD_COUNTS: [usize; 4] = [1, 2, 4, 8];
+const ADDS_PER_THREAD: usize = 10_000;
+
 fn bench_latest_reading_holder(c: &mut Criterion) {
     let holder = LatestReadingHolder::new(10);
 
@@ -70,10 +88,77 @@ fn bench_start_record(c: &mut Criterion) {
     });
 }
 
+// Each thread hammers its own reading, so any slowdown as threads are added comes from false sharing.
+// Compare `cargo bench` with `cargo bench --features padded-readings` to see the effect of the padded layout.
+fn bench_parallel_add_scaling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel add, one reading per thread");
+    for threads in SCALING_THREAD_COUNTS {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let recorder: RapidRecorder<_, ScalingReadings> = RapidRecorder::new(1000, 8);
+        let mut handle = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Step),
+        );
+        handle.start_record(0);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    pool.install(|| {
+                        (0..threads).into_par_iter().for_each(|t| {
+                            let reading = ScalingReadings::from(t);
+                            for i in 0..ADDS_PER_THREAD {
+                                handle.add(reading, i as f64);
+                            }
+                        });
+                    });
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_parallel_holder_scaling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("LatestReadingHolder.set_value, one slot per thread");
+    for threads in SCALING_THREAD_COUNTS {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let holder = LatestReadingHolder::new(8);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    pool.install(|| {
+                        (0..threads).into_par_iter().for_each(|t| {
+                            for i in 0..ADDS_PER_THREAD {
+                                holder.set_value(t, i as f64);
+                            }
+                        });
+                    });
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_latest_reading_holder,
     bench_recorder_add,
-    bench_start_record
+    bench_start_record,
+    bench_parallel_add_scaling,
+    bench_parallel_holder_scaling
 );
 criterion_main!(benches);
@@ -6,7 +6,7 @@ use std::time::Duration;
 use strum_macros::EnumIter;
 
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 pub enum ExampleReadingNames {
     InternalVariable0,
     InternalVariable1,
@@ -17,7 +17,7 @@ impl_rapid_recorder_named_usize!(ExampleReadingNames);
 
 pub fn main() {
     env_logger::init();
-    let max_reading_types_to_track = 50; // Your ReadingNames enum should have no more than this many variants, the larger this value is the more overhead there is, but anything below 10k should still be plenty fast
+    let max_reading_types_to_track = 4; // Must match the number of variants in your ReadingNames enum, the larger this value is the more overhead there is, but anything below 10k should still be plenty fast
     let rapid_recorder = RapidRecorder::new(1_000_000, max_reading_types_to_track);
     let mut rr_group = rapid_recorder.add_group(
         RapidRecorderGroup::new()
@@ -25,111 +25,121 @@ pub fn main() {
             .index_type(DefaultIndexDimmension::Step),
     );
 
-    // Create a thread to show "UI" updates by periodically reading from recorder
-    let ui_thread = thread::spawn(move || {
-        for i in 0..10 {
-            thread::sleep(Duration::from_millis(100));
-            info!("UI Thread: Checking simulation status...");
-
-            // In a real app, this would access the recorder and display its data
-            // through a UI framework. Here we just log the current status.
-            info!("UI Thread: Simulation running, iteration {}/10", i + 1);
-        }
-    });
-
-    // Ideal use case:
-    // parallelism within a single-thread primary record index
-    // to keep track of internal variables deeply burried within high performance, parallel code
-    // avoids having to expose, emit or return the internal variables in performance-sensitive code
-    for i in 0..1000 {
-        rr_group.start_record(i);
-        // spawn threads
-        (0..3).into_par_iter().for_each(|j| {
-            match j {
-                0 => {
-                    // do work
-                    let internal_variable_0 = 5.0;
-                    rr_group.add(ExampleReadingNames::InternalVariable0, internal_variable_0)
-
-                    // do work
-                }
-                1 => {
-                    // do work
-                    let internal_variable_1 = 5.0;
-                    rr_group.add(ExampleReadingNames::InternalVariable1, internal_variable_1)
-
-                    // do work
-                }
-                2 => {
-                    // do work
-                    let internal_variable_2 = 5.0;
-                    rr_group.add(ExampleReadingNames::InternalVariable2, internal_variable_2)
+    // Scoped threads let the "UI" thread borrow the recorder while the main thread keeps recording
+    thread::scope(|s| {
+        // Create a thread to show "UI" updates by periodically reading from recorder
+        let ui_thread = s.spawn(|| {
+            for i in 0..10 {
+                thread::sleep(Duration::from_millis(100));
+                info!("UI Thread: Checking simulation status...");
+
+                // history_view copies the events without consuming them, so the final analysis below still gets everything.
+                // In a real app, this would display the data through a UI framework. Here we just log the latest step.
+                let view = rapid_recorder.history_view();
+                let latest_step = view.iter().map(|event| event.id).max();
+                info!(
+                    "UI Thread: Simulation running, iteration {}/10, {} records so far, latest step {:?}",
+                    i + 1,
+                    view.len(),
+                    latest_step
+                );
+            }
+        });
 
-                    // do work
-                }
-                3 => {
-                    // spawn more threads
-                    (0..3).into_par_iter().for_each(|k| {
-                        match k {
-                            0 => {
-                                // do work
-                                let internal_variable_3 = 5.0;
-                                rr_group.add(
-                                    ExampleReadingNames::InternalVariable3,
-                                    internal_variable_3,
-                                )
-
-                                // do work
-                            }
-                            1 => {
-                                // do work
-                                let internal_variable_1 = 5.0;
-                                rr_group.add(
-                                    ExampleReadingNames::InternalVariable1,
-                                    internal_variable_1,
-                                )
-
-                                // do work
+        // Ideal use case:
+        // parallelism within a single-thread primary record index
+        // to keep track of internal variables deeply burried within high performance, parallel code
+        // avoids having to expose, emit or return the internal variables in performance-sensitive code
+        for i in 0..1000 {
+            rr_group.start_record(i);
+            // spawn threads
+            (0..3).into_par_iter().for_each(|j| {
+                match j {
+                    0 => {
+                        // do work
+                        let internal_variable_0 = 5.0;
+                        rr_group.add(ExampleReadingNames::InternalVariable0, internal_variable_0)
+
+                        // do work
+                    }
+                    1 => {
+                        // do work
+                        let internal_variable_1 = 5.0;
+                        rr_group.add(ExampleReadingNames::InternalVariable1, internal_variable_1)
+
+                        // do work
+                    }
+                    2 => {
+                        // do work
+                        let internal_variable_2 = 5.0;
+                        rr_group.add(ExampleReadingNames::InternalVariable2, internal_variable_2)
+
+                        // do work
+                    }
+                    3 => {
+                        // spawn more threads
+                        (0..3).into_par_iter().for_each(|k| {
+                            match k {
+                                0 => {
+                                    // do work
+                                    let internal_variable_3 = 5.0;
+                                    rr_group.add(
+                                        ExampleReadingNames::InternalVariable3,
+                                        internal_variable_3,
+                                    )
+
+                                    // do work
+                                }
+                                1 => {
+                                    // do work
+                                    let internal_variable_1 = 5.0;
+                                    rr_group.add(
+                                        ExampleReadingNames::InternalVariable1,
+                                        internal_variable_1,
+                                    )
+
+                                    // do work
+                                }
+                                2 => {
+                                    // do work
+                                    let internal_variable_2 = 5.0;
+                                    rr_group.add(
+                                        ExampleReadingNames::InternalVariable2,
+                                        internal_variable_2,
+                                    )
+
+                                    // do work
+                                }
+                                3 => {
+                                    // do work
+                                }
+                                _ => {
+                                    // Handle any other cases
+                                    // This branch should never execute with the current 0..3 range
+                                }
                             }
-                            2 => {
-                                // do work
-                                let internal_variable_2 = 5.0;
-                                rr_group.add(
-                                    ExampleReadingNames::InternalVariable2,
-                                    internal_variable_2,
-                                )
-
-                                // do work
-                            }
-                            3 => {
-                                // do work
-                            }
-                            _ => {
-                                // Handle any other cases
-                                // This branch should never execute with the current 0..3 range
-                            }
-                        }
-                    });
-                    let internal_variable_3 = 5.0;
-                    rr_group.add(ExampleReadingNames::InternalVariable3, internal_variable_3)
-
-                    // do work
-                }
-                _ => {
-                    // Handle any other cases
-                    // This branch should never execute with the current 0..3 range
+                        });
+                        let internal_variable_3 = 5.0;
+                        rr_group.add(ExampleReadingNames::InternalVariable3, internal_variable_3)
+
+                        // do work
+                    }
+                    _ => {
+                        // Handle any other cases
+                        // This branch should never execute with the current 0..3 range
+                    }
                 }
-            }
-        });
+            });
 
-        // Simulate some work in the main thread
-        if i % 200 == 0 {
-            info!("Main thread: Simulation progress {}%", i / 10);
+            // Simulate some work in the main thread
+            if i % 200 == 0 {
+                info!("Main thread: Simulation progress {}%", i / 10);
+            }
         }
-    }
 
-    // Wait for UI thread to complete
-    ui_thread.join().unwrap();
+        // Wait for UI thread to complete
+        ui_thread.join().unwrap();
+    });
 
     // Simulation complete, retrieve and process results
     info!("Simulation complete, processing results...");
@@ -1,10 +1,9 @@
-use log::info;
 use rapid_recorder::prelude::*;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use strum_macros::EnumIter;
 
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 pub enum ExampleReadingNames {
     InternalVariable0,
     InternalVariable1,
@@ -27,9 +26,10 @@ pub fn main() {
 
     // Adding same reading name multiple times per record:
     // performance will be good, but the value recorded will be inconsistent, as only one value will be recorded per reading name (whichever is the last thread to write it)
+    // if you need every thread's value, use `RapidRecorder::reading_mode` to sum, average, etc. them instead
     for i in 0..1000 {
         rapid_recorder_group_1.start_record(i);
-        (0..10).into_par_iter().for_each(|j| {
+        (0..10).into_par_iter().for_each(|_j| {
             // do work
             let internal_variable_0 = 5.0;
             rapid_recorder_group_1.add(ExampleReadingNames::InternalVariable0, internal_variable_0)
@@ -47,7 +47,7 @@ pub fn main() {
                 .index_type(DefaultIndexDimmension::Step),
         );
         rapid_recorder_group_some.start_record(i);
-        (0..100).into_par_iter().for_each(|j| {
+        (0..100).into_par_iter().for_each(|_j| {
             // do work
             // record things
             // etc.
@@ -1,4 +1,3 @@
-use log::info;
 use rapid_recorder::latest_reading_holder::LatestReadingHolder;
 use rapid_recorder::prelude::*;
 use std::hint::black_box;
@@ -6,7 +5,7 @@ use std::time::{Duration, Instant};
 use strum_macros::EnumIter;
 
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 pub enum ExampleReadingNames {
     InternalVariable0,
     InternalVariable1,
@@ -28,7 +27,7 @@ pub fn main() {
 
         let start = Instant::now();
         for i in 0..ITERATIONS {
-            holder.set_value((i as usize % READING_NAMES_MAX) as usize, 5.0);
+            holder.set_value(i as usize % READING_NAMES_MAX, 5.0);
             black_box(&holder);
         }
         let duration = start.elapsed();
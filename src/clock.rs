@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Where the recorder gets the time for `RRTimestamps` and `start_record_now`, set with `RapidRecorder::clock`.
+///
+/// `now` is called from every thread that saves a record, so it should be cheap and must never go backwards.
+pub trait RecorderClock: Send + Sync + 'static {
+    /// Time since the clock started
+    fn now(&self) -> Duration;
+}
+
+/// Monotonic real time from `std::time::Instant`, the default
+pub struct RRInstantClock {
+    start: Instant,
+}
+
+impl RRInstantClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RRInstantClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecorderClock for RRInstantClock {
+    #[inline(always)]
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Real time from the x86 timestamp counter, cheaper to read than `Instant` on most machines.
+///
+/// Ticks are converted to time with a rate measured against `Instant` when the clock is created. Only use this on
+/// CPUs with an invariant TSC (anything from the last decade), otherwise the rate changes with the CPU frequency.
+/// Threads on cores whose counters aren't synchronised may read a slightly earlier time, which is held at the latest
+/// time handed out so the clock never goes backwards.
+#[cfg(target_arch = "x86_64")]
+pub struct RRTscClock {
+    start_ticks: u64,
+    nanos_per_tick: f64,
+    latest_nanos: AtomicU64,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl RRTscClock {
+    /// Calibrates over 10ms
+    pub fn new() -> Self {
+        Self::calibrated_over(Duration::from_millis(10))
+    }
+
+    /// Calibrates by counting ticks while sleeping for `period`, longer periods give a more accurate rate
+    pub fn calibrated_over(period: Duration) -> Self {
+        let (instant_before, ticks_before) = (Instant::now(), read_tsc());
+        std::thread::sleep(period);
+        // the thread may have moved to a core whose counter is behind
+        let (elapsed, ticks) = (
+            instant_before.elapsed(),
+            read_tsc().saturating_sub(ticks_before),
+        );
+        Self {
+            start_ticks: read_tsc(),
+            nanos_per_tick: elapsed.as_nanos() as f64 / ticks.max(1) as f64,
+            latest_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// The measured length of one tick in nanoseconds
+    pub fn nanos_per_tick(&self) -> f64 {
+        self.nanos_per_tick
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Default for RRTscClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl RecorderClock for RRTscClock {
+    #[inline(always)]
+    fn now(&self) -> Duration {
+        let ticks = read_tsc().saturating_sub(self.start_ticks);
+        let nanos = (ticks as f64 * self.nanos_per_tick) as u64;
+        let latest = self.latest_nanos.load(Ordering::Relaxed);
+        if nanos <= latest {
+            return Duration::from_nanos(latest);
+        }
+        // another thread may have handed out a later time since the load
+        Duration::from_nanos(
+            self.latest_nanos
+                .fetch_max(nanos, Ordering::Relaxed)
+                .max(nanos),
+        )
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn read_tsc() -> u64 {
+    // SAFETY: rdtsc has no preconditions and is available on every x86_64 CPU
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// A clock that only moves when you move it, for simulation time and deterministic tests.
+///
+/// Clones share the same time, so keep one to drive the clock and hand another to `RapidRecorder::clock`.
+#[derive(Clone, Default)]
+pub struct RRSimulatedClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl RRSimulatedClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Jumps to `time`, ignored if that would move the clock backwards
+    pub fn set(&self, time: Duration) {
+        self.nanos
+            .fetch_max(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.nanos
+            .fetch_add(by.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl RecorderClock for RRSimulatedClock {
+    #[inline(always)]
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
@@ -1,12 +1,98 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use crate::histogram::RRHistogram;
+use crate::moments::RRMoments;
 use crate::named_usize::ValidRapidRecorderNamedUsize;
+use crate::timestamps::RRTimestamps;
+use crate::value::RRValue;
+
+/// How a `RawRREvent` stores its readings, chosen with `RapidRecorder::event_encoding`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RREventEncoding {
+    /// One value and one changed flag per reading type, cheapest to save when most readings change every record
+    #[default]
+    Dense,
+    /// Only the `(reading_index, value)` pairs that changed, much smaller when few readings change per record
+    Sparse,
+}
+
+pub enum RawRRReadings {
+    Dense {
+        values: Vec<RRValue>,
+        changed: Vec<bool>,
+    },
+    Sparse(Vec<(usize, RRValue)>),
+}
+
+impl RawRRReadings {
+    pub fn encoding(&self) -> RREventEncoding {
+        match self {
+            RawRRReadings::Dense { .. } => RREventEncoding::Dense,
+            RawRRReadings::Sparse(_) => RREventEncoding::Sparse,
+        }
+    }
+
+    /// Iterates over the `(reading_index, value)` pairs that changed during the record, regardless of encoding
+    pub fn changed(&self) -> ChangedReadings<'_> {
+        match self {
+            RawRRReadings::Dense { values, changed } => ChangedReadings::Dense {
+                values,
+                changed,
+                next: 0,
+            },
+            RawRRReadings::Sparse(pairs) => ChangedReadings::Sparse(pairs.iter()),
+        }
+    }
+}
+
+pub enum ChangedReadings<'a> {
+    Dense {
+        values: &'a [RRValue],
+        changed: &'a [bool],
+        next: usize,
+    },
+    Sparse(std::slice::Iter<'a, (usize, RRValue)>),
+}
+
+impl Iterator for ChangedReadings<'_> {
+    type Item = (usize, RRValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ChangedReadings::Dense {
+                values,
+                changed,
+                next,
+            } => {
+                while *next < values.len() {
+                    let i = *next;
+                    *next += 1;
+                    if changed[i] {
+                        return Some((i, values[i]));
+                    }
+                }
+                None
+            }
+            ChangedReadings::Sparse(pairs) => pairs.next().copied(),
+        }
+    }
+}
 
 pub struct RawRREvent {
-    pub readings: Vec<f64>,
-    pub changed: Vec<bool>,
+    pub readings: RawRRReadings,
     pub record_id: usize,
     pub id_type: usize,
+    /// `(reading_index, counts)` for every `Histogram` reading that saw a value during the record
+    pub histograms: Vec<(usize, RRHistogram)>,
+    /// `(reading_index, moments)` for every `MeanVariance` reading that saw a value during the record
+    pub moments: Vec<(usize, RRMoments)>,
+    pub timestamps: RRTimestamps,
+    /// Name of the group that saved the event, see `RapidRecorderGroup::name`
+    pub group: Option<Arc<str>>,
+    /// `(index_type, record_id)` of the records of every parent group this event was nested in, outermost first,
+    /// empty for top level groups, see `RapidRecorderGroupHandle::add_child_group`
+    pub parents: Vec<(usize, usize)>,
 }
 impl RawRREvent {
     pub fn to_rr_event<
@@ -16,14 +102,32 @@ impl RawRREvent {
         &self,
     ) -> RREvent<ReadingName, IndexDimmension> {
         let mut values_map = HashMap::new();
-        for (i, value) in self.readings.iter().enumerate() {
-            if !self.changed[i] {
-                continue;
-            }
-            values_map.insert(ReadingName::from(i), *value);
+        let mut typed_values_map = HashMap::new();
+        for (i, value) in self.readings.changed() {
+            values_map.insert(ReadingName::from(i), value.as_f64());
+            typed_values_map.insert(ReadingName::from(i), value);
         }
         RREvent {
             values: values_map,
+            typed_values: typed_values_map,
+            histograms: self
+                .histograms
+                .iter()
+                .map(|(i, histogram)| (ReadingName::from(*i), histogram.clone()))
+                .collect(),
+            moments: self
+                .moments
+                .iter()
+                .map(|(i, moments)| (ReadingName::from(*i), *moments))
+                .collect(),
+            timestamps: self.timestamps,
+            group: self.group.clone(),
+            path: self
+                .parents
+                .iter()
+                .chain(std::iter::once(&(self.id_type, self.record_id)))
+                .map(|(index_type, id)| (IndexDimmension::from(*index_type), *id))
+                .collect(),
             id: self.record_id,
             iteration_index: IndexDimmension::from(self.id_type),
         }
@@ -33,7 +137,51 @@ pub struct RREvent<
     ReadingName: ValidRapidRecorderNamedUsize,
     IndexDimmension: ValidRapidRecorderNamedUsize,
 > {
+    /// Every changed reading converted to `f64`, lossy for large integers, see `typed_values` for the exact values
     pub values: HashMap<ReadingName, f64>,
+    /// Every changed reading with the type it was added with
+    pub typed_values: HashMap<ReadingName, RRValue>,
+    /// Bucket counts of every `RRReadingMode::Histogram` reading that saw a value during the record
+    pub histograms: HashMap<ReadingName, RRHistogram>,
+    /// Count, mean and variance of every `RRReadingMode::MeanVariance` reading that saw a value during the record
+    pub moments: HashMap<ReadingName, RRMoments>,
+    /// When the record was started and saved
+    pub timestamps: RRTimestamps,
+    /// Name of the group that saved the event, `None` for unnamed groups
+    pub group: Option<Arc<str>>,
+    /// The full index path, outermost group first and ending with this event's own `(iteration_index, id)`,
+    /// e.g. `[(Run, 3), (Step, 17), (I, 4)]`
+    pub path: Vec<(IndexDimmension, usize)>,
     pub id: usize,
     pub iteration_index: IndexDimmension,
 }
+impl<ReadingName: ValidRapidRecorderNamedUsize, IndexDimmension: ValidRapidRecorderNamedUsize>
+    RREvent<ReadingName, IndexDimmension>
+{
+    /// The reading as the type it was added with, `None` if it did not change this record or was added as a different type,
+    /// e.g. `event.get::<bool>(MyReadings::Converged)`
+    pub fn get<T: TryFrom<RRValue>>(&self, reading_name: ReadingName) -> Option<T> {
+        self.typed_values
+            .get(&reading_name)
+            .and_then(|value| T::try_from(*value).ok())
+    }
+
+    /// The record ID at the given level of the index path, e.g. `event.index_at(DefaultIndexDimmension::Step)`,
+    /// `None` if the event wasn't nested under a group with that index type
+    pub fn index_at(&self, level: IndexDimmension) -> Option<usize> {
+        self.path
+            .iter()
+            .find(|(index_type, _)| *index_type == level)
+            .map(|(_, id)| *id)
+    }
+
+    /// The bucket counts of a `Histogram` reading, `None` if no value was added to it this record
+    pub fn histogram(&self, reading_name: ReadingName) -> Option<&RRHistogram> {
+        self.histograms.get(&reading_name)
+    }
+
+    /// The count, mean and variance of a `MeanVariance` reading, `None` if no value was added to it this record
+    pub fn moments(&self, reading_name: ReadingName) -> Option<&RRMoments> {
+        self.moments.get(&reading_name)
+    }
+}
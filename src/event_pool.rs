@@ -0,0 +1,65 @@
+use crossbeam::queue::ArrayQueue;
+
+use crate::event::{RREventEncoding, RawRRReadings};
+use crate::latest_reading_holder::LatestReadingHolder;
+use crate::value::RRValue;
+
+/// Recycled reading storage, so saving an event copies into an existing buffer instead of allocating.
+///
+/// Buffers leave the pool when an event is saved and come back when the event is consumed through
+/// the recorder's history functions or handed back with `RapidRecorder::recycle`.
+pub(crate) struct EventPool {
+    free: ArrayQueue<RawRRReadings>,
+    reading_types: usize,
+}
+
+impl EventPool {
+    pub(crate) fn new(size: usize, reading_types: usize, encoding: RREventEncoding) -> Self {
+        let free = ArrayQueue::new(size);
+        for _ in 0..size {
+            let _ = free.push(Self::allocate(reading_types, encoding));
+        }
+        Self {
+            free,
+            reading_types,
+        }
+    }
+
+    fn allocate(reading_types: usize, encoding: RREventEncoding) -> RawRRReadings {
+        match encoding {
+            RREventEncoding::Dense => RawRRReadings::Dense {
+                values: vec![RRValue::default(); reading_types],
+                changed: vec![false; reading_types],
+            },
+            RREventEncoding::Sparse => RawRRReadings::Sparse(Vec::with_capacity(reading_types)),
+        }
+    }
+
+    /// Fills a recycled buffer from the holder, only allocates if the pool has run dry
+    #[inline(always)]
+    pub(crate) fn snapshot(
+        &self,
+        holder: &LatestReadingHolder,
+        encoding: RREventEncoding,
+    ) -> RawRRReadings {
+        let mut readings = match self.free.pop() {
+            Some(readings) if readings.encoding() == encoding => readings,
+            _ => Self::allocate(self.reading_types, encoding),
+        };
+        match &mut readings {
+            RawRRReadings::Dense { values, changed } => holder.snapshot_into(values, changed),
+            RawRRReadings::Sparse(pairs) => holder.sparse_snapshot_into(pairs),
+        }
+        readings
+    }
+
+    /// Returns a buffer to the pool, if the pool is already full the buffer is simply freed
+    #[inline(always)]
+    pub(crate) fn give_back(&self, readings: RawRRReadings) {
+        let _ = self.free.push(readings);
+    }
+
+    pub(crate) fn available(&self) -> usize {
+        self.free.len()
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::named_usize::ValidRapidRecorderNamedUsize;
+use crate::recording::unix_seconds;
+use crate::value::RRValue;
+use crate::{RRDuplicateEventIdHandling, RRGroupedHistory};
+
+/// What to write in a CSV cell for a reading that did not change during the record
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RRUnchangedReadings {
+    /// Leave the cell empty
+    #[default]
+    Blank,
+    /// Write `NaN`, handy for tools that expect every cell to be numeric
+    NaN,
+    /// Repeat the last value recorded for that reading under the same index type, blank until the first value
+    ForwardFill,
+}
+
+/// Options for `RapidRecorder::export_csv`
+#[derive(Debug, Clone, Default)]
+pub struct RRCsvOptions {
+    pub unchanged_readings: RRUnchangedReadings,
+    pub duplicate_event_id_handling: RRDuplicateEventIdHandling,
+    /// Add `started_s`, `saved_s`, `started_unix_s` and `saved_unix_s` columns after `record_id`, see `RRTimestamps`.
+    /// The first two are seconds since the recorder was created, the unix columns are blank without `wall_clock_timestamps`
+    pub timestamps: bool,
+    /// Add a `group` column after `index_type` with the name of the group that saved each event, see `RapidRecorderGroup::name`
+    pub group_column: bool,
+    /// Only write and drain the events of the group with this name, other groups' events stay in the history
+    pub only_group: Option<Arc<str>>,
+}
+
+/// Appends `field` to `row`, quoted per RFC 4180 if it contains a comma, quote or line break
+fn push_csv_field(row: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        row.push('"');
+        row.push_str(&field.replace('"', "\"\""));
+        row.push('"');
+    } else {
+        row.push_str(field);
+    }
+}
+
+/// Writes the header and one row per event, grouped by group name (unnamed first), then index type in enum order,
+/// and sorted by record ID
+pub(crate) fn write_csv<
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+>(
+    history: RRGroupedHistory<ReadingName, IndexDimmension>,
+    mut writer: impl Write,
+    options: RRCsvOptions,
+) -> io::Result<usize> {
+    let reading_names: Vec<ReadingName> = ReadingName::iter().collect();
+    let mut header = String::from("index_type");
+    if options.group_column {
+        header.push_str(",group");
+    }
+    header.push_str(",record_id");
+    if options.timestamps {
+        header.push_str(",started_s,saved_s,started_unix_s,saved_unix_s");
+    }
+    for name in &reading_names {
+        header.push_str(&format!(",{:?}", name));
+    }
+    writeln!(writer, "{}", header)?;
+
+    let mut groups: Vec<_> = history.into_iter().collect();
+    groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut rows = 0;
+    for (group, mut by_index_type) in groups {
+        for index_type in IndexDimmension::iter() {
+            let Some(events) = by_index_type.remove(&index_type) else {
+                continue;
+            };
+            let mut last_values: HashMap<ReadingName, RRValue> = HashMap::new();
+            for event in events {
+                let mut row = format!("{:?}", index_type);
+                if options.group_column {
+                    row.push(',');
+                    push_csv_field(&mut row, group.as_deref().unwrap_or_default());
+                }
+                row.push_str(&format!(",{}", event.id));
+                if options.timestamps {
+                    let timestamps = event.timestamps;
+                    row.push_str(&format!(
+                        ",{},{}",
+                        timestamps.started.as_secs_f64(),
+                        timestamps.saved.as_secs_f64()
+                    ));
+                    for wall in [timestamps.started_wall, timestamps.saved_wall] {
+                        row.push(',');
+                        if let Some(wall) = wall {
+                            row.push_str(&unix_seconds(wall).to_string());
+                        }
+                    }
+                }
+                for name in &reading_names {
+                    row.push(',');
+                    match event.typed_values.get(name) {
+                        Some(value) => {
+                            row.push_str(&value.to_string());
+                            last_values.insert(*name, *value);
+                        }
+                        None => match options.unchanged_readings {
+                            RRUnchangedReadings::Blank => {}
+                            RRUnchangedReadings::NaN => row.push_str("NaN"),
+                            RRUnchangedReadings::ForwardFill => {
+                                if let Some(value) = last_values.get(name) {
+                                    row.push_str(&value.to_string());
+                                }
+                            }
+                        },
+                    }
+                }
+                writeln!(writer, "{}", row)?;
+                rows += 1;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(rows)
+}
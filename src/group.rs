@@ -1,6 +1,10 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::{
     defaults::{DefaultIndexDimmension, DefaultSamplingFrequency},
     named_usize::ValidRapidRecorderNamedUsize,
+    sampling::{RRDeadband, RRSampleSchedule, RRTrigger, RawRRTrigger},
 };
 
 pub struct RapidRecorderGroup<
@@ -11,6 +15,18 @@ pub struct RapidRecorderGroup<
     pub sample_rate_value: Option<usize>,
     pub index_type: Option<IndexDimmension>,
     pub index_type_value: Option<usize>,
+    /// Stamped on every event this group saves, see `RREvent::group`
+    pub name: Option<Arc<str>>,
+    /// Minimum time between saved records, see `RapidRecorderGroup::sample_interval`
+    pub sample_interval: Option<Duration>,
+    /// Extra rule for which record IDs to save, see `RapidRecorderGroup::sample_schedule`
+    pub sample_schedule: Option<RRSampleSchedule>,
+    /// Shifts every sampling rule by this many IDs, see `RapidRecorderGroup::sample_offset`
+    pub sample_offset: usize,
+    /// Skip records whose readings barely moved, see `RapidRecorderGroup::deadband`
+    pub deadband: Option<RRDeadband>,
+    /// Only save records around a trigger, see `RapidRecorderGroup::trigger`
+    pub(crate) trigger: Option<RawRRTrigger>,
 }
 
 impl<SamplingFrequency: ValidRapidRecorderNamedUsize, IndexDimmension: ValidRapidRecorderNamedUsize>
@@ -22,6 +38,12 @@ impl<SamplingFrequency: ValidRapidRecorderNamedUsize, IndexDimmension: ValidRapi
             sample_rate_value: None,
             index_type: None,
             index_type_value: None,
+            name: None,
+            sample_interval: None,
+            sample_schedule: None,
+            sample_offset: 0,
+            deadband: None,
+            trigger: None,
         }
     }
     pub fn sample_rate(
@@ -33,6 +55,12 @@ impl<SamplingFrequency: ValidRapidRecorderNamedUsize, IndexDimmension: ValidRapi
             sample_rate_value: Some(rate.into()),
             index_type: self.index_type,
             index_type_value: self.index_type_value,
+            name: self.name,
+            sample_interval: self.sample_interval,
+            sample_schedule: self.sample_schedule,
+            sample_offset: self.sample_offset,
+            deadband: self.deadband,
+            trigger: self.trigger,
         }
     }
 
@@ -45,6 +73,89 @@ impl<SamplingFrequency: ValidRapidRecorderNamedUsize, IndexDimmension: ValidRapi
             sample_rate_value: self.sample_rate_value,
             index_type: Some(index_type),
             index_type_value: Some(index_type.into()),
+            name: self.name,
+            sample_interval: self.sample_interval,
+            sample_schedule: self.sample_schedule,
+            sample_offset: self.sample_offset,
+            deadband: self.deadband,
+            trigger: self.trigger,
+        }
+    }
+
+    /// Save at most one record every `interval` of the recorder's clock, so recording density stays even in time however
+    /// fast the loop runs. Use with `RRSimulatedClock` to sample in simulation time. Records that start sooner are skipped
+    /// and counted as sampled out. Combines with `sample_rate`, a record has to pass both, and `sample_rate` is optional
+    /// when this is set.
+    pub fn sample_interval(
+        self,
+        interval: Duration,
+    ) -> RapidRecorderGroup<SamplingFrequency, IndexDimmension> {
+        RapidRecorderGroup {
+            sample_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Only save records whose ID is in `schedule`, e.g. log spaced IDs for long convergence runs.
+    /// Combines with `sample_rate` and `sample_interval`, a record has to pass all of them
+    pub fn sample_schedule(
+        self,
+        schedule: RRSampleSchedule,
+    ) -> RapidRecorderGroup<SamplingFrequency, IndexDimmension> {
+        RapidRecorderGroup {
+            sample_schedule: Some(schedule),
+            ..self
+        }
+    }
+
+    /// Shift the sampling by `offset` IDs: with `EveryTen` and an offset of 3 the group saves 3, 13, 23, ...
+    /// IDs below the offset are never saved
+    pub fn sample_offset(
+        self,
+        offset: usize,
+    ) -> RapidRecorderGroup<SamplingFrequency, IndexDimmension> {
+        RapidRecorderGroup {
+            sample_offset: offset,
+            ..self
+        }
+    }
+
+    /// Only save a record if at least one of its readings moved past `deadband` since the last record this group saved.
+    ///
+    /// Decided when the record is saved, after the other sampling options. Skipped records count as sampled out.
+    /// Histogram and mean/variance readings are not compared.
+    pub fn deadband(
+        self,
+        deadband: RRDeadband,
+    ) -> RapidRecorderGroup<SamplingFrequency, IndexDimmension> {
+        RapidRecorderGroup {
+            deadband: Some(deadband),
+            ..self
+        }
+    }
+
+    /// Flight recorder mode: hold records in a rolling window and only save them when `trigger` fires, see `RRTrigger`.
+    /// Applied after every other sampling option, including the deadband. `ReadingName` has to be the recorder's reading
+    /// type, adding the group to a recorder with other readings panics
+    pub fn trigger<ReadingName: ValidRapidRecorderNamedUsize>(
+        self,
+        trigger: RRTrigger<ReadingName>,
+    ) -> RapidRecorderGroup<SamplingFrequency, IndexDimmension> {
+        RapidRecorderGroup {
+            trigger: Some(trigger.into()),
+            ..self
+        }
+    }
+
+    /// Names the group so its events can be told apart from other groups with the same index type,
+    /// see `RapidRecorder::sorted_history_by_group`
+    pub fn name(
+        self,
+        name: impl Into<Arc<str>>,
+    ) -> RapidRecorderGroup<SamplingFrequency, IndexDimmension> {
+        RapidRecorderGroup {
+            name: Some(name.into()),
+            ..self
         }
     }
 }
@@ -1,4 +1,25 @@
-use crate::{RapidRecorder, group::RapidRecorderGroup, named_usize::ValidRapidRecorderNamedUsize};
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::{
+    RapidRecorder, event::RawRREvent, group::RapidRecorderGroup,
+    latest_reading_holder::LatestReadingHolder, named_usize::ValidRapidRecorderNamedUsize,
+    sampling::RawRRTrigger, value::RRValue,
+};
+
+const NO_RECORD: usize = usize::MAX;
+
+/// A handle's current record index, shared with its child groups so they can tell which record they are nested in
+pub(crate) struct IndexCell {
+    index_type: usize,
+    value: AtomicUsize,
+    parent: Option<Arc<IndexCell>>,
+}
 
 pub struct RapidRecorderGroupHandle<
     'a,
@@ -6,11 +27,190 @@ pub struct RapidRecorderGroupHandle<
     ReadingName: ValidRapidRecorderNamedUsize,
     IndexDimmension: ValidRapidRecorderNamedUsize,
 > {
-    group: RapidRecorderGroup<SamplingFrequency, IndexDimmension>,
+    state: GroupState<SamplingFrequency, IndexDimmension>,
     recorder: &'a RapidRecorder<IndexDimmension, ReadingName>,
-    sample_rate: usize,
+    // each handle records into its own readings, so groups saving at different rates never take each other's values
+    readings: LatestReadingHolder,
     index_value: Option<usize>,
     should_save_next: bool,
+    record_started: Duration,
+    cell: Arc<IndexCell>,
+    // `(index_type, record_id)` of every ancestor, outermost first, as they were when the current record started
+    parents: Vec<(usize, usize)>,
+}
+
+/// A group's options and the sampling state that outlives a single record. Only needs `&self`, so
+/// `RapidRecorderSharedGroupHandle` can sample and save records from many threads at once
+pub(crate) struct GroupState<
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> {
+    pub(crate) group: RapidRecorderGroup<SamplingFrequency, IndexDimmension>,
+    pub(crate) index_type: usize,
+    pub(crate) sample_rate: usize,
+    // anything beyond a plain stride, checked once here so simple groups keep the single modulo in `start_record`
+    pub(crate) complex_sampling: bool,
+    // nanoseconds when the last record that passed the interval check started
+    last_sampled: AtomicU64,
+    // reading values of the last saved record, only used with a deadband
+    last_saved: Mutex<Vec<Option<f64>>>,
+    // only used with a trigger
+    flight_recorder: Mutex<FlightRecorder>,
+}
+
+const NOT_SAMPLED: u64 = u64::MAX;
+
+#[derive(Default)]
+struct FlightRecorder {
+    window: VecDeque<RawRREvent>,
+    // records still to save after the last trigger
+    remaining_after: usize,
+}
+
+impl<SamplingFrequency: ValidRapidRecorderNamedUsize, IndexDimmension: ValidRapidRecorderNamedUsize>
+    GroupState<SamplingFrequency, IndexDimmension>
+{
+    /// Panics if the group's trigger watches a reading the recorder doesn't have
+    pub(crate) fn new<ReadingName: ValidRapidRecorderNamedUsize>(
+        group: RapidRecorderGroup<SamplingFrequency, IndexDimmension>,
+        recorder: &RapidRecorder<IndexDimmension, ReadingName>,
+    ) -> Self {
+        if let Some(trigger) = &group.trigger {
+            trigger.check_reading::<ReadingName>(recorder.latest_readings.capacity());
+        }
+        Self {
+            index_type: group.index_type_value.unwrap(),
+            sample_rate: group.sample_rate_value.unwrap_or(1),
+            complex_sampling: group.sample_interval.is_some()
+                || group.sample_schedule.is_some()
+                || group.sample_offset > 0,
+            group,
+            last_sampled: AtomicU64::new(NOT_SAMPLED),
+            last_saved: Mutex::new(Vec::new()),
+            flight_recorder: Mutex::new(FlightRecorder::default()),
+        }
+    }
+
+    /// Whether the record starting now with `unique_id` should be saved according to the group's sampling options
+    pub(crate) fn sample<ReadingName: ValidRapidRecorderNamedUsize>(
+        &self,
+        recorder: &RapidRecorder<IndexDimmension, ReadingName>,
+        unique_id: usize,
+    ) -> bool {
+        let Some(id) = unique_id.checked_sub(self.group.sample_offset) else {
+            return false;
+        };
+        if !id.is_multiple_of(self.sample_rate) {
+            return false;
+        }
+        if let Some(schedule) = &self.group.sample_schedule
+            && !schedule.includes(id)
+        {
+            return false;
+        }
+        if let Some(interval) = self.group.sample_interval {
+            let now = recorder.now().as_nanos() as u64;
+            let interval = interval.as_nanos() as u64;
+            let mut last = self.last_sampled.load(Ordering::Relaxed);
+            loop {
+                if last != NOT_SAMPLED && now.saturating_sub(last) < interval {
+                    return false;
+                }
+                // only one of several threads starting a record in the same interval gets it
+                match self.last_sampled.compare_exchange_weak(
+                    last,
+                    now,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(current) => last = current,
+                }
+            }
+        }
+        true
+    }
+
+    /// Saves the record in `readings`, applying the deadband and trigger if the group has them
+    #[inline(always)]
+    pub(crate) fn save<ReadingName: ValidRapidRecorderNamedUsize>(
+        &self,
+        recorder: &RapidRecorder<IndexDimmension, ReadingName>,
+        readings: &LatestReadingHolder,
+        index_value: usize,
+        started: Duration,
+        parents: &[(usize, usize)],
+    ) {
+        if self.group.deadband.is_none() && self.group.trigger.is_none() {
+            recorder.save_scope(
+                readings,
+                self.index_type,
+                index_value,
+                started,
+                self.group.name.as_ref(),
+                parents,
+            );
+            return;
+        }
+        let event = recorder.snapshot_event(
+            readings,
+            self.index_type,
+            index_value,
+            started,
+            self.group.name.as_ref(),
+            parents,
+        );
+        if let Some(deadband) = self.group.deadband {
+            let mut last_saved = self.last_saved.lock();
+            last_saved.resize(readings.capacity(), None);
+            if !deadband.check_and_update(&event.readings, &mut last_saved) {
+                recorder.count_sampled_out();
+                recorder.recycle(event);
+                return;
+            }
+        }
+        match &self.group.trigger {
+            Some(trigger) => self.hold_for_trigger(recorder, trigger, event),
+            None => recorder.push_event(event),
+        }
+    }
+
+    fn hold_for_trigger<ReadingName: ValidRapidRecorderNamedUsize>(
+        &self,
+        recorder: &RapidRecorder<IndexDimmension, ReadingName>,
+        trigger: &RawRRTrigger,
+        event: RawRREvent,
+    ) {
+        let mut flight_recorder = self.flight_recorder.lock();
+        if trigger.fires(&event.readings) {
+            for held in flight_recorder.window.drain(..) {
+                recorder.push_event(held);
+            }
+            recorder.push_event(event);
+            flight_recorder.remaining_after = trigger.after;
+        } else if flight_recorder.remaining_after > 0 {
+            flight_recorder.remaining_after -= 1;
+            recorder.push_event(event);
+        } else {
+            flight_recorder.window.push_back(event);
+            if flight_recorder.window.len() > trigger.before {
+                let expired = flight_recorder.window.pop_front().unwrap();
+                recorder.count_sampled_out();
+                recorder.recycle(expired);
+            }
+        }
+    }
+
+    /// Discards the records a trigger never fired for, called when the handle goes away
+    pub(crate) fn retire<ReadingName: ValidRapidRecorderNamedUsize>(
+        &mut self,
+        recorder: &RapidRecorder<IndexDimmension, ReadingName>,
+    ) {
+        for held in self.flight_recorder.get_mut().window.drain(..) {
+            recorder.count_sampled_out();
+            recorder.recycle(held);
+        }
+    }
 }
 
 impl<
@@ -24,32 +224,118 @@ impl<
         group: RapidRecorderGroup<SamplingFrequency, IndexDimmension>,
         recorder: &'a RapidRecorder<IndexDimmension, ReadingName>,
     ) -> Self {
-        let sample_rate = group.sample_rate_value.unwrap();
+        Self::with_parent(group, recorder, None)
+    }
+
+    fn with_parent(
+        group: RapidRecorderGroup<SamplingFrequency, IndexDimmension>,
+        recorder: &'a RapidRecorder<IndexDimmension, ReadingName>,
+        parent: Option<Arc<IndexCell>>,
+    ) -> Self {
+        let state = GroupState::new(group, recorder);
+        let cell = Arc::new(IndexCell {
+            index_type: state.index_type,
+            value: AtomicUsize::new(NO_RECORD),
+            parent,
+        });
         Self {
-            group,
+            state,
             recorder,
-            sample_rate,
+            readings: recorder.new_reading_scope(),
             index_value: None,
             should_save_next: true,
+            record_started: recorder.now(),
+            cell,
+            parents: Vec::new(),
         }
     }
+
+    /// Creates a group nested inside this one, e.g. a Step group inside a Run group. Its events carry the current
+    /// record of this group and all of its ancestors, see `RREvent::path`.
+    ///
+    /// The child doesn't borrow this handle, so both can keep starting records. The path is captured when the child's
+    /// record starts, so start the child's records after this group's.
+    pub fn add_child_group<ChildSampleRate: ValidRapidRecorderNamedUsize>(
+        &self,
+        group: RapidRecorderGroup<ChildSampleRate, IndexDimmension>,
+    ) -> RapidRecorderGroupHandle<'a, ChildSampleRate, ReadingName, IndexDimmension> {
+        RapidRecorderGroupHandle::with_parent(group, self.recorder, Some(self.cell.clone()))
+    }
+    /// Sets the reading for the current record. Accepts `f64`, `i64`, `u64`, `bool` and the smaller integer types,
+    /// the value keeps its type in the saved event, see `RREvent::get`.
+    /// Only this group's records see the value, other groups on the same recorder keep their own readings
     #[inline(always)]
-    pub fn add(&self, reading_name: ReadingName, value: f64) {
-        if self.should_save_next {
-            self.recorder._add_reading(reading_name.into(), value);
+    pub fn add(&self, reading_name: ReadingName, value: impl Into<RRValue>) {
+        if self.should_save_next && self.recorder.is_recording() {
+            self.readings
+                .set_typed_value(reading_name.into(), value.into());
         }
     }
     /// This causes the previously started record to be saved, and starts a new record with the given unique_id. If you do not call this method, no records will be saved.
     /// The final record will be saved when the RapidRecorderGroupHandle is dropped, no need to call this method at the end of your recording loop manually.
     /// This should be called in the same thread where the group was created
+    /// While the recorder is paused, the record is not saved and doesn't count as sampled out
     pub fn start_record(&mut self, unique_id: usize) {
         if self.should_save_next {
             self._save_record();
         }
         self.index_value = Some(unique_id);
-        if self.sample_rate > 1 {
-            self.should_save_next = self.index_value.unwrap().is_multiple_of(self.sample_rate);
+        // children need to know where they are even when this group's own record is sampled out
+        self.cell.value.store(unique_id, Ordering::Relaxed);
+        if !self.recorder.is_recording() {
+            self.should_save_next = false;
+            return;
+        }
+        if self.state.complex_sampling {
+            self.should_save_next = self.state.sample(self.recorder, unique_id);
+            if !self.should_save_next {
+                self.recorder.count_sampled_out();
+            }
+        } else if self.state.sample_rate > 1 {
+            self.should_save_next = unique_id.is_multiple_of(self.state.sample_rate);
+            if !self.should_save_next {
+                self.recorder.count_sampled_out();
+            }
+        } else {
+            // a `RecordGuard` stops saving when it finishes its record
+            self.should_save_next = true;
+        }
+        if self.should_save_next {
+            self.record_started = self.recorder.now();
+            self.capture_parents();
+        }
+    }
+
+    /// Starts a record like `start_record` and saves it as soon as the returned guard is dropped, including on early
+    /// returns and panics, instead of when the next record starts. Readings added after that and before the next
+    /// record are ignored.
+    /// The guard derefs to the handle, so use it to add readings and create child groups
+    pub fn record(
+        &mut self,
+        unique_id: usize,
+    ) -> RecordGuard<'_, 'a, SamplingFrequency, ReadingName, IndexDimmension> {
+        self.start_record(unique_id);
+        RecordGuard { handle: self }
+    }
+
+    fn capture_parents(&mut self) {
+        self.parents.clear();
+        let mut ancestor = self.cell.parent.as_deref();
+        while let Some(cell) = ancestor {
+            let value = cell.value.load(Ordering::Relaxed);
+            if value != NO_RECORD {
+                self.parents.push((cell.index_type, value));
+            }
+            ancestor = cell.parent.as_deref();
         }
+        self.parents.reverse();
+    }
+    /// Starts a record whose ID is the current time of the recorder's clock in nanoseconds, and returns that ID.
+    /// Meant for groups indexed by `DefaultIndexDimmension::Time`, with `RRSimulatedClock` the IDs are simulation time
+    pub fn start_record_now(&mut self) -> usize {
+        let unique_id = self.recorder.now().as_nanos() as usize;
+        self.start_record(unique_id);
+        unique_id
     }
     /// Not recommended to call this manually...
     /// You ONLY need to call this function manually if you are retrieving the history
@@ -57,11 +343,54 @@ impl<
     /// Note that if you call this and then start another record then there will be two records in the history with the same index value, but not necessarily the same readings
     /// So you will have to account for that when processing the history
     pub fn _save_record(&self) {
-        if self.index_value.is_some() {
-            self.recorder._save_event(
-                self.group.index_type_value.unwrap(),
-                self.index_value.unwrap(),
-            );
+        let Some(index_value) = self.index_value else {
+            return;
+        };
+        self.state.save(
+            self.recorder,
+            &self.readings,
+            index_value,
+            self.record_started,
+            &self.parents,
+        );
+    }
+}
+
+/// A record started by `RapidRecorderGroupHandle::record`, saved when dropped
+pub struct RecordGuard<
+    'h,
+    'a,
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> {
+    handle: &'h mut RapidRecorderGroupHandle<'a, SamplingFrequency, ReadingName, IndexDimmension>,
+}
+
+impl<
+    'a,
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> Deref for RecordGuard<'_, 'a, SamplingFrequency, ReadingName, IndexDimmension>
+{
+    type Target = RapidRecorderGroupHandle<'a, SamplingFrequency, ReadingName, IndexDimmension>;
+
+    fn deref(&self) -> &Self::Target {
+        self.handle
+    }
+}
+
+impl<
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> Drop for RecordGuard<'_, '_, SamplingFrequency, ReadingName, IndexDimmension>
+{
+    fn drop(&mut self) {
+        if self.handle.should_save_next {
+            self.handle._save_record();
+            self.handle.should_save_next = false;
         }
     }
 }
@@ -75,6 +404,9 @@ impl<
 > Drop for RapidRecorderGroupHandle<'a, SamplingFrequency, ReadingName, IndexDimmension>
 {
     fn drop(&mut self) {
-        self._save_record();
+        if self.should_save_next {
+            self._save_record();
+        }
+        self.state.retire(self.recorder);
     }
 }
@@ -0,0 +1,149 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::latest_reading_holder::{ReadingSlot, slot};
+
+/// Bucket layout for `RRReadingMode::Histogram`.
+///
+/// Both layouts split `[min, max)` into `count` buckets, with an extra underflow bucket for values below `min`
+/// (and NaN) and an overflow bucket for values at or above `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RRHistogramBuckets {
+    /// Equal width buckets
+    Linear { min: f64, max: f64, count: usize },
+    /// Buckets of equal width in log space, `min` must be positive. Good for values spanning several orders of magnitude
+    Log { min: f64, max: f64, count: usize },
+}
+
+impl RRHistogramBuckets {
+    /// Number of counters including the underflow and overflow buckets
+    pub fn counters(&self) -> usize {
+        self.count() + 2
+    }
+
+    /// Number of buckets between `min` and `max`, not counting underflow and overflow
+    pub fn count(&self) -> usize {
+        match *self {
+            RRHistogramBuckets::Linear { count, .. } | RRHistogramBuckets::Log { count, .. } => {
+                count
+            }
+        }
+    }
+
+    /// Which counter a value belongs in, 0 is the underflow bucket and `counters() - 1` the overflow bucket
+    #[inline(always)]
+    pub fn index_of(&self, value: f64) -> usize {
+        let (position, count) = match *self {
+            RRHistogramBuckets::Linear { min, max, count } => ((value - min) / (max - min), count),
+            RRHistogramBuckets::Log { min, max, count } => {
+                ((value / min).ln() / (max / min).ln(), count)
+            }
+        };
+        // NaN, including the log of a non-positive value, lands in underflow
+        if position.is_nan() || position < 0.0 {
+            return 0;
+        }
+        if position >= 1.0 {
+            return count + 1;
+        }
+        1 + ((position * count as f64) as usize).min(count - 1)
+    }
+
+    /// The `[low, high)` value range of counter `index`, infinite for the underflow and overflow buckets
+    pub fn range_of(&self, index: usize) -> (f64, f64) {
+        let count = self.count();
+        if index == 0 {
+            return (f64::NEG_INFINITY, self.edge(0));
+        }
+        if index > count {
+            return (self.edge(count), f64::INFINITY);
+        }
+        (self.edge(index - 1), self.edge(index))
+    }
+
+    fn edge(&self, i: usize) -> f64 {
+        match *self {
+            RRHistogramBuckets::Linear { min, max, count } => {
+                min + (max - min) * i as f64 / count as f64
+            }
+            RRHistogramBuckets::Log { min, max, count } => {
+                min * (max / min).powf(i as f64 / count as f64)
+            }
+        }
+    }
+}
+
+/// Bucket counts of a histogram reading for one record, see `RREvent::histograms`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RRHistogram {
+    pub buckets: RRHistogramBuckets,
+    /// One counter per bucket, `counts[0]` is underflow and the last entry is overflow
+    pub counts: Vec<u64>,
+}
+
+impl RRHistogram {
+    /// Total number of values added during the record
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn underflow(&self) -> u64 {
+        self.counts[0]
+    }
+
+    pub fn overflow(&self) -> u64 {
+        self.counts[self.counts.len() - 1]
+    }
+
+    /// Iterates over `((low, high), count)` for every bucket including underflow and overflow
+    pub fn iter(&self) -> impl Iterator<Item = ((f64, f64), u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (self.buckets.range_of(i), *count))
+    }
+}
+
+/// The lock-free counters behind a histogram reading
+pub(crate) struct AtomicHistogram {
+    buckets: RRHistogramBuckets,
+    counts: Vec<ReadingSlot<AtomicU64>>,
+}
+
+impl AtomicHistogram {
+    pub(crate) fn new(buckets: RRHistogramBuckets) -> Self {
+        assert!(
+            buckets.count() > 0,
+            "a histogram needs at least one bucket between min and max"
+        );
+        if let RRHistogramBuckets::Log { min, .. } = buckets {
+            assert!(min > 0.0, "log scale histogram buckets need a positive min");
+        }
+        Self {
+            buckets,
+            counts: (0..buckets.counters())
+                .map(|_| slot(AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn record(&self, value: f64) {
+        self.counts[self.buckets.index_of(value)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Takes the counts and resets them for the next record, `None` if nothing was added
+    pub(crate) fn take(&self) -> Option<RRHistogram> {
+        let counts: Vec<u64> = self
+            .counts
+            .iter()
+            .map(|c| c.swap(0, Ordering::Relaxed))
+            .collect();
+        if counts.iter().all(|c| *c == 0) {
+            return None;
+        }
+        Some(RRHistogram {
+            buckets: self.buckets,
+            counts,
+        })
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::event::RREvent;
+use crate::named_usize::ValidRapidRecorderNamedUsize;
+use crate::{RRDuplicateEventIdHandling, RRGroupedHistory, RRLevelHistory};
+
+/// Groups events by their iteration index, applies the duplicate handling, and sorts each group by event ID
+pub(crate) fn organize_history<
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+>(
+    events: impl IntoIterator<Item = RREvent<ReadingName, IndexDimmension>>,
+    duplicate_event_id_handling: RRDuplicateEventIdHandling,
+) -> HashMap<IndexDimmension, Vec<RREvent<ReadingName, IndexDimmension>>> {
+    let mut organized: HashMap<IndexDimmension, Vec<RREvent<ReadingName, IndexDimmension>>> =
+        HashMap::new();
+
+    for rr_event in events {
+        organized
+            .entry(rr_event.iteration_index)
+            .or_default()
+            .push(rr_event);
+    }
+
+    for events in organized.values_mut() {
+        // Handle duplicate event IDs according to the specified strategy
+        match duplicate_event_id_handling {
+            RRDuplicateEventIdHandling::KeepOnlyFirst => {
+                let mut seen_ids = HashSet::new();
+                events.retain(|e| seen_ids.insert(e.id));
+            }
+            RRDuplicateEventIdHandling::KeepOnlyLast => {
+                let mut seen_ids = HashSet::new();
+                events.reverse();
+                events.retain(|e| seen_ids.insert(e.id));
+                events.reverse();
+            }
+            RRDuplicateEventIdHandling::KeepBoth => {
+                // Keep all events, no filtering needed
+            }
+        }
+
+        // Sort events by ID, stable so duplicates keep their recording order
+        events.sort_by_key(|e| e.id);
+    }
+
+    organized
+}
+
+/// Splits events by the group that saved them, then organizes each group on its own so duplicate handling
+/// never drops one group's event because another group used the same record ID
+pub(crate) fn organize_history_by_group<
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+>(
+    events: impl IntoIterator<Item = RREvent<ReadingName, IndexDimmension>>,
+    duplicate_event_id_handling: RRDuplicateEventIdHandling,
+) -> RRGroupedHistory<ReadingName, IndexDimmension> {
+    let mut by_group: HashMap<Option<Arc<str>>, Vec<RREvent<ReadingName, IndexDimmension>>> =
+        HashMap::new();
+    for rr_event in events {
+        by_group
+            .entry(rr_event.group.clone())
+            .or_default()
+            .push(rr_event);
+    }
+    by_group
+        .into_iter()
+        .map(|(group, events)| (group, organize_history(events, duplicate_event_id_handling)))
+        .collect()
+}
+
+/// Splits events by their record ID at `level` of the index path, `None` for events not nested under that level,
+/// then organizes each slice on its own so inner record IDs that repeat for every outer record aren't seen as duplicates
+pub(crate) fn organize_history_by_level<
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+>(
+    events: impl IntoIterator<Item = RREvent<ReadingName, IndexDimmension>>,
+    level: IndexDimmension,
+    duplicate_event_id_handling: RRDuplicateEventIdHandling,
+) -> RRLevelHistory<ReadingName, IndexDimmension> {
+    let mut by_level: HashMap<Option<usize>, Vec<RREvent<ReadingName, IndexDimmension>>> =
+        HashMap::new();
+    for rr_event in events {
+        by_level
+            .entry(rr_event.index_at(level))
+            .or_default()
+            .push(rr_event);
+    }
+    by_level
+        .into_iter()
+        .map(|(id, events)| (id, organize_history(events, duplicate_event_id_handling)))
+        .collect()
+}
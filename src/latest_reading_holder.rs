@@ -1,35 +1,300 @@
-use atomic_float::AtomicF64;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+
+use crate::histogram::{AtomicHistogram, RRHistogram};
+use crate::moments::{AtomicMoments, RRMoments};
+use crate::reading_mode::RRReadingMode;
+use crate::value::{KIND_F64, RRValue};
+
+// the kind of an accumulating slot while one thread replaces its value with the first value of a new type
+const KIND_SWITCHING: u8 = u8::MAX;
+
+/// With the `padded-readings` feature every slot sits on its own cache line, so threads writing
+/// different readings never bounce the same line between cores. Costs 128 bytes per slot on x86_64.
+#[cfg(feature = "padded-readings")]
+pub type ReadingSlot<T> = crossbeam::utils::CachePadded<T>;
+/// Without the `padded-readings` feature slots are packed contiguously, which is the most compact layout
+/// and the fastest when readings are written from a single thread
+#[cfg(not(feature = "padded-readings"))]
+pub type ReadingSlot<T> = T;
+
+#[cfg(feature = "padded-readings")]
+#[inline(always)]
+pub(crate) fn slot<T>(value: T) -> ReadingSlot<T> {
+    crossbeam::utils::CachePadded::new(value)
+}
+#[cfg(not(feature = "padded-readings"))]
+#[inline(always)]
+pub(crate) fn slot<T>(value: T) -> ReadingSlot<T> {
+    value
+}
+
 pub struct LatestReadingHolder {
     // pub segment_size: usize,
     // pub readings: Vec<Mutex<Vec<f64>>>,
-    pub readings: Vec<AtomicF64>,
-    pub changed: Vec<AtomicBool>,
+    /// Raw bits of the latest value, interpreted according to the matching entry in `kinds`
+    pub readings: Vec<ReadingSlot<AtomicU64>>,
+    /// Which `RRValue` variant each slot currently holds
+    pub kinds: Vec<ReadingSlot<AtomicU8>>,
+    pub changed: Vec<ReadingSlot<AtomicBool>>,
+    /// How `set_typed_value` combines values for each slot, see `RRReadingMode`
+    pub modes: Vec<RRReadingMode>,
+    /// Number of values added this record, only used by the `Count` and `Mean` modes
+    pub counts: Vec<ReadingSlot<AtomicU64>>,
+    /// How many times each value was replaced before a snapshot captured it, per slot so parallel writers don't contend.
+    /// Shared by every scope made with `new_scope`, so the recorder sees the counts of live group handles
+    pub overwritten: Arc<[ReadingSlot<AtomicUsize>]>,
+    /// Bucket counters for slots in `Histogram` mode, `None` for every other slot
+    pub(crate) histograms: Vec<Option<AtomicHistogram>>,
+    /// Indices of the `Some` entries in `histograms`, so saving doesn't scan every slot
+    histogram_slots: Vec<usize>,
+    /// Accumulators for slots in `MeanVariance` mode, `None` for every other slot
+    pub(crate) moments: Vec<Option<AtomicMoments>>,
+    moment_slots: Vec<usize>,
 }
 impl LatestReadingHolder {
     pub fn new(capacity: usize) -> Self {
         Self {
-            readings: (0..capacity).map(|_| AtomicF64::new(0.0)).collect(),
-            changed: (0..capacity).map(|_| AtomicBool::new(false)).collect(),
+            readings: (0..capacity)
+                .map(|_| slot(AtomicU64::new(0.0f64.to_bits())))
+                .collect(),
+            kinds: (0..capacity)
+                .map(|_| slot(AtomicU8::new(RRValue::default().to_bits().0)))
+                .collect(),
+            changed: (0..capacity)
+                .map(|_| slot(AtomicBool::new(false)))
+                .collect(),
+            modes: vec![RRReadingMode::Last; capacity],
+            counts: (0..capacity).map(|_| slot(AtomicU64::new(0))).collect(),
+            overwritten: (0..capacity).map(|_| slot(AtomicUsize::new(0))).collect(),
+            histograms: (0..capacity).map(|_| None).collect(),
+            histogram_slots: Vec::new(),
+            moments: (0..capacity).map(|_| None).collect(),
+            moment_slots: Vec::new(),
+        }
+    }
+
+    /// A new empty holder with the same capacity and reading modes, used to give every group handle its own readings.
+    /// Overwritten readings are counted in this holder's counters
+    pub fn new_scope(&self) -> Self {
+        let mut scope = Self::new(self.capacity());
+        scope.overwritten = self.overwritten.clone();
+        for (index, mode) in self.modes.iter().enumerate() {
+            if *mode != RRReadingMode::Last {
+                scope.set_mode(index, *mode);
+            }
         }
+        scope
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.readings.len()
+    }
+
+    /// Changes how values for a slot are combined, resetting its accumulator. Only possible before the holder is shared
+    pub fn set_mode(&mut self, index: usize, mode: RRReadingMode) {
+        self.modes[index] = mode;
+        self.kinds[index].store(KIND_F64, Ordering::Relaxed);
+        self.readings[index].store(mode.identity(KIND_F64), Ordering::Relaxed);
+        self.counts[index].store(0, Ordering::Relaxed);
+        self.histograms[index] = match mode {
+            RRReadingMode::Histogram(buckets) => Some(AtomicHistogram::new(buckets)),
+            _ => None,
+        };
+        self.histogram_slots = (0..self.capacity())
+            .filter(|i| self.histograms[*i].is_some())
+            .collect();
+        self.moments[index] = match mode {
+            RRReadingMode::MeanVariance => Some(AtomicMoments::new()),
+            _ => None,
+        };
+        self.moment_slots = (0..self.capacity())
+            .filter(|i| self.moments[*i].is_some())
+            .collect();
     }
 
     #[inline(always)]
     pub fn set_value(&self, index: usize, value: f64) {
-        self.readings[index].store(value, Ordering::Relaxed);
-        self.changed[index].store(true, Ordering::Relaxed);
+        self.set_typed_value(index, RRValue::F64(value));
+    }
+
+    #[inline(always)]
+    pub fn set_typed_value(&self, index: usize, value: RRValue) {
+        if self.modes[index] != RRReadingMode::Last {
+            return self.accumulate(index, value);
+        }
+        let (kind, bits) = value.to_bits();
+        self.readings[index].store(bits, Ordering::Relaxed);
+        // a reading almost always keeps its type, so avoid dirtying the line with a redundant store
+        if self.kinds[index].load(Ordering::Relaxed) != kind {
+            self.kinds[index].store(kind, Ordering::Relaxed);
+        }
+        // plain load first so the common case (first write this record) stays a pair of stores
+        if self.changed[index].load(Ordering::Relaxed) {
+            self.overwritten[index].fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.changed[index].store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[inline(always)]
+    fn accumulate(&self, index: usize, value: RRValue) {
+        let mode = self.modes[index];
+        match mode {
+            RRReadingMode::Count => {
+                self.counts[index].fetch_add(1, Ordering::Relaxed);
+            }
+            RRReadingMode::Mean => {
+                let _ = self.readings[index].fetch_update(
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    |sum| Some((f64::from_bits(sum) + value.as_f64()).to_bits()),
+                );
+                self.counts[index].fetch_add(1, Ordering::Relaxed);
+            }
+            RRReadingMode::Histogram(_) => {
+                // bucket counts are saved separately from the readings, so the changed flag stays untouched
+                if let Some(histogram) = &self.histograms[index] {
+                    histogram.record(value.as_f64());
+                }
+                return;
+            }
+            RRReadingMode::MeanVariance => {
+                if let Some(moments) = &self.moments[index] {
+                    moments.record(value.as_f64());
+                }
+                return;
+            }
+            _ => {
+                let (kind, bits) = value.to_bits();
+                let slot_kind = &self.kinds[index];
+                loop {
+                    match slot_kind.load(Ordering::Acquire) {
+                        current if current == kind => {
+                            let _ = self.readings[index].fetch_update(
+                                Ordering::Relaxed,
+                                Ordering::Relaxed,
+                                |current| Some(mode.combine(kind, current, bits)),
+                            );
+                            break;
+                        }
+                        KIND_SWITCHING => std::hint::spin_loop(),
+                        current => {
+                            // first value of a new type, there is nothing of the same type to combine it with.
+                            // One thread claims the switch, the others wait for its value and combine with it
+                            if slot_kind
+                                .compare_exchange(
+                                    current,
+                                    KIND_SWITCHING,
+                                    Ordering::Acquire,
+                                    Ordering::Relaxed,
+                                )
+                                .is_ok()
+                            {
+                                self.readings[index].store(bits, Ordering::Relaxed);
+                                slot_kind.store(kind, Ordering::Release);
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if !self.changed[index].load(Ordering::Relaxed) {
+            self.changed[index].store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn overwritten_count(&self) -> usize {
+        self.overwritten
+            .iter()
+            .map(|a| a.load(Ordering::Relaxed))
+            .sum()
     }
 
-    pub fn snapshot(&self) -> (Vec<f64>, Vec<bool>) {
+    #[inline(always)]
+    fn load(&self, index: usize) -> RRValue {
+        RRValue::from_bits(
+            self.kinds[index].load(Ordering::Relaxed),
+            self.readings[index].load(Ordering::Relaxed),
+        )
+    }
+
+    /// Reads a slot for a snapshot, resetting accumulators so the next record starts fresh
+    #[inline(always)]
+    fn take(&self, index: usize) -> RRValue {
+        match self.modes[index] {
+            RRReadingMode::Last | RRReadingMode::Histogram(_) | RRReadingMode::MeanVariance => {
+                self.load(index)
+            }
+            RRReadingMode::Count => RRValue::U64(self.counts[index].swap(0, Ordering::Relaxed)),
+            RRReadingMode::Mean => {
+                let sum = f64::from_bits(self.readings[index].swap(0, Ordering::Relaxed));
+                let count = self.counts[index].swap(0, Ordering::Relaxed);
+                RRValue::F64(if count == 0 { 0.0 } else { sum / count as f64 })
+            }
+            mode => {
+                let kind = self.kinds[index].load(Ordering::Relaxed);
+                let bits = self.readings[index].swap(mode.identity(kind), Ordering::Relaxed);
+                RRValue::from_bits(kind, bits)
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> (Vec<RRValue>, Vec<bool>) {
         (
-            self.readings
-                .iter()
-                .map(|a| a.load(Ordering::Relaxed))
-                .collect(),
+            (0..self.capacity()).map(|i| self.take(i)).collect(),
             self.changed
                 .iter()
                 .map(|a| a.swap(false, Ordering::Relaxed))
                 .collect(),
         )
     }
+
+    /// Same as `snapshot` but writes into existing buffers of length `capacity` instead of allocating
+    pub fn snapshot_into(&self, values: &mut [RRValue], changed: &mut [bool]) {
+        for (i, (value, changed)) in values.iter_mut().zip(changed.iter_mut()).enumerate() {
+            *value = self.take(i);
+            *changed = self.changed[i].swap(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Like `snapshot` but only returns the `(index, value)` pairs that changed, untouched flags are not written to
+    pub fn sparse_snapshot(&self) -> Vec<(usize, RRValue)> {
+        self.changed
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.load(Ordering::Relaxed) && c.swap(false, Ordering::Relaxed))
+            .map(|(i, _)| (i, self.take(i)))
+            .collect()
+    }
+
+    /// Same as `sparse_snapshot` but reuses the given vector, which is cleared first
+    pub fn sparse_snapshot_into(&self, pairs: &mut Vec<(usize, RRValue)>) {
+        pairs.clear();
+        for (i, c) in self.changed.iter().enumerate() {
+            if c.load(Ordering::Relaxed) && c.swap(false, Ordering::Relaxed) {
+                pairs.push((i, self.take(i)));
+            }
+        }
+    }
+
+    /// Takes the bucket counts of every histogram slot that saw a value this record, resetting them
+    pub fn histogram_snapshot(&self) -> Vec<(usize, RRHistogram)> {
+        self.histogram_slots
+            .iter()
+            .filter_map(|&i| {
+                let histogram = self.histograms[i].as_ref()?.take()?;
+                Some((i, histogram))
+            })
+            .collect()
+    }
+
+    /// Takes the moments of every `MeanVariance` slot that saw a value this record, resetting them
+    pub fn moments_snapshot(&self) -> Vec<(usize, RRMoments)> {
+        self.moment_slots
+            .iter()
+            .filter_map(|&i| Some((i, self.moments[i].as_ref()?.take()?)))
+            .collect()
+    }
 }
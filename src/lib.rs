@@ -1,33 +1,96 @@
 use crossbeam::queue::ArrayQueue;
 
+use log::warn;
+use parking_lot::RwLock;
+
 use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 
-use crate::event::{RREvent, RawRREvent};
+use crate::clock::{RRInstantClock, RecorderClock};
+use crate::event::{RREvent, RREventEncoding, RawRREvent, RawRRReadings};
+use crate::event_pool::EventPool;
+use crate::export::{RRCsvOptions, write_csv};
 use crate::group::RapidRecorderGroup;
 use crate::group_handle::RapidRecorderGroupHandle;
+use crate::history::{organize_history, organize_history_by_group, organize_history_by_level};
 use crate::latest_reading_holder::LatestReadingHolder;
 use crate::named_usize::ValidRapidRecorderNamedUsize;
+use crate::overflow::{OverflowCounters, RROverflowCounts, RROverflowPolicy};
+use crate::reading_mode::RRReadingMode;
+use crate::recording::RecordingWriter;
+use crate::save_gate::{SaveGate, SavePass};
+use crate::shared_group_handle::RapidRecorderSharedGroupHandle;
+use crate::sink::{DrainThread, RREventSink};
+use crate::stats::{RRStats, RecorderCounters};
+use crate::timestamps::RRTimestamps;
+use crate::value::RRValue;
 
+pub mod clock;
 pub mod defaults;
 pub mod event;
+mod event_pool;
+pub mod export;
 pub mod group;
 pub mod group_handle;
+pub mod histogram;
+mod history;
 pub mod latest_reading_holder;
+pub mod moments;
 pub mod named_usize;
+pub mod overflow;
+pub mod reading_mode;
+pub mod recording;
+pub mod sampling;
+mod save_gate;
+pub mod shared_group_handle;
+pub mod sink;
+pub mod stats;
+pub mod timestamps;
+pub mod value;
 pub mod prelude {
     pub use crate::RapidRecorder;
+    #[cfg(target_arch = "x86_64")]
+    pub use crate::clock::RRTscClock;
+    pub use crate::clock::{RRInstantClock, RRSimulatedClock, RecorderClock};
     pub use crate::defaults::{DefaultIndexDimmension, DefaultSamplingFrequency};
+    pub use crate::event::RREventEncoding;
+    pub use crate::export::{RRCsvOptions, RRUnchangedReadings};
     pub use crate::group::RapidRecorderGroup;
+    pub use crate::histogram::{RRHistogram, RRHistogramBuckets};
     pub use crate::impl_rapid_recorder_named_usize;
+    pub use crate::moments::RRMoments;
     pub use crate::named_usize::ValidRapidRecorderNamedUsize;
+    pub use crate::overflow::RROverflowPolicy;
+    pub use crate::reading_mode::RRReadingMode;
+    pub use crate::recording::{RecordingReader, RecordingWriter};
+    pub use crate::sampling::{RRDeadband, RRSampleSchedule, RRTrigger};
+    pub use crate::sink::{RREventSink, RRWriterSink};
+    pub use crate::stats::RRStats;
+    pub use crate::timestamps::RRTimestamps;
+    pub use crate::value::RRValue;
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum RRDuplicateEventIdHandling {
     KeepOnlyFirst,
     KeepOnlyLast,
+    #[default]
     KeepBoth,
 }
 
+/// History partitioned by the name of the group that saved each event (`None` for unnamed groups),
+/// then by index type, see `RapidRecorder::sorted_history_by_group`
+pub type RRGroupedHistory<ReadingName, IndexDimmension> =
+    HashMap<Option<Arc<str>>, HashMap<IndexDimmension, Vec<RREvent<ReadingName, IndexDimmension>>>>;
+
+/// History sliced by the record ID at one level of the index path (`None` for events not nested under that level),
+/// then by index type, see `RapidRecorder::sorted_history_by_level`
+pub type RRLevelHistory<ReadingName, IndexDimmension> =
+    HashMap<Option<usize>, HashMap<IndexDimmension, Vec<RREvent<ReadingName, IndexDimmension>>>>;
+
 /// The main recorder for storing events and managing recording groups.
 ///
 /// RapidRecorder is responsible for tracking internal variables efficiently in
@@ -41,8 +104,21 @@ pub struct RapidRecorder<
     IndexDimmension: ValidRapidRecorderNamedUsize,
     ReadingName: ValidRapidRecorderNamedUsize,
 > {
-    buffer: ArrayQueue<RawRREvent>,
+    buffer: Arc<ArrayQueue<RawRREvent>>,
     latest_readings: LatestReadingHolder,
+    event_encoding: RREventEncoding,
+    event_pool: Option<Arc<EventPool>>,
+    overflow_policy: RROverflowPolicy,
+    overflow_counters: OverflowCounters,
+    counters: RecorderCounters,
+    // pops share this, `history_view` takes it exclusively so consumers never see the buffer while it is emptied
+    view_lock: RwLock<()>,
+    // holds saves off while `history_view` takes the buffer apart
+    save_gate: SaveGate,
+    drain: Option<DrainThread>,
+    clock: Box<dyn RecorderClock>,
+    wall_epoch: Option<SystemTime>,
+    paused: AtomicBool,
     phantom: std::marker::PhantomData<IndexDimmension>,
     phantom2: std::marker::PhantomData<ReadingName>,
 }
@@ -69,19 +145,175 @@ impl<IterationIndex: ValidRapidRecorderNamedUsize, ReadingName: ValidRapidRecord
             }
         }
         Self {
-            buffer: ArrayQueue::new(max_history_length),
+            buffer: Arc::new(ArrayQueue::new(max_history_length)),
             latest_readings: LatestReadingHolder::new(max_reading_types),
+            event_encoding: RREventEncoding::default(),
+            event_pool: None,
+            overflow_policy: RROverflowPolicy::default(),
+            overflow_counters: OverflowCounters::default(),
+            counters: RecorderCounters::default(),
+            view_lock: RwLock::new(()),
+            save_gate: SaveGate::new(),
+            drain: None,
+            clock: Box::new(RRInstantClock::new()),
+            wall_epoch: None,
+            paused: AtomicBool::new(false),
             phantom: std::marker::PhantomData,
             phantom2: std::marker::PhantomData,
         }
     }
 
+    /// Choose how repeated `add` calls for `reading` within one record are combined, defaults to `RRReadingMode::Last`.
+    ///
+    /// Use `Sum`, `Min`, `Max`, `Count` or `Mean` when many threads add the same reading during a record,
+    /// their contributions are combined atomically instead of racing to be the last write.
+    /// `Histogram` keeps the whole distribution of a record's values as bucket counts, `MeanVariance` their mean and spread.
+    pub fn reading_mode(mut self, reading: ReadingName, mode: RRReadingMode) -> Self {
+        self.latest_readings.set_mode(reading.into(), mode);
+        self
+    }
+
+    /// Choose how saved events store their readings, defaults to `RREventEncoding::Dense`.
+    /// Use `RREventEncoding::Sparse` when you have many reading types but only a few change per record
+    pub fn event_encoding(mut self, encoding: RREventEncoding) -> Self {
+        self.event_encoding = encoding;
+        // buffers preallocated for the old encoding would be thrown away and reallocated on every save
+        if self.event_pool.is_some() {
+            self = self.preallocate_events();
+        }
+        self
+    }
+
+    /// Preallocates reading storage for every slot in the history (`max_history_length × max_reading_types`),
+    /// so saving an event copies into a recycled buffer instead of hitting the allocator.
+    ///
+    /// Buffers go back to the pool when events are consumed through `convenient_pop` or the sorted history functions.
+    /// If you pop from `raw_history` yourself, hand the events back with `recycle` or the pool will slowly run dry
+    /// and saving falls back to allocating. Call this before `drain_to`, so the drain thread can hand buffers back once
+    /// the sink is done with them.
+    pub fn preallocate_events(mut self) -> Self {
+        if self.drain.is_some() {
+            warn!(
+                "preallocate_events was called after drain_to, drained events will not be recycled"
+            );
+        }
+        self.event_pool = Some(Arc::new(EventPool::new(
+            self.buffer.capacity() + 1,
+            self.latest_readings.capacity(),
+            self.event_encoding,
+        )));
+        self
+    }
+
+    /// Returns an event's reading storage to the preallocated pool, a no-op drop if `preallocate_events` was not used
+    pub fn recycle(&self, event: RawRREvent) {
+        if let Some(pool) = &self.event_pool {
+            pool.give_back(event.readings);
+        }
+    }
+
+    /// How many preallocated buffers are ready for the next save, `None` if `preallocate_events` was not used
+    pub fn pooled_buffers_available(&self) -> Option<usize> {
+        self.event_pool.as_ref().map(|pool| pool.available())
+    }
+
+    /// Starts a background thread that pops every saved event and forwards it to `sink`, checking the buffer every `poll_interval`.
+    ///
+    /// This keeps a small history buffer from ever filling up, so recording can run indefinitely. When the recorder is dropped
+    /// the thread drains whatever is left, calls `RREventSink::flush` and is joined. Events go to the sink instead of staying
+    /// in the history, so the history functions will mostly come back empty. Calling this twice replaces the previous sink.
+    /// With `preallocate_events`, events the sink hands back from `RREventSink::accept` are recycled.
+    pub fn drain_to(mut self, sink: impl RREventSink, poll_interval: Duration) -> Self {
+        self.drain = Some(DrainThread::spawn(
+            self.buffer.clone(),
+            self.event_pool.clone(),
+            sink,
+            poll_interval,
+        ));
+        self
+    }
+
+    /// Also stamp events with wall clock time (`RRTimestamps::started_wall` and `saved_wall`), off by default.
+    ///
+    /// The system clock is read once here, event times are this plus the recorder clock's time since, so it adds nothing
+    /// per record. Only meaningful with a clock that runs in real time, not `RRSimulatedClock`
+    pub fn wall_clock_timestamps(mut self) -> Self {
+        self.wall_epoch = SystemTime::now().checked_sub(self.clock.now());
+        self
+    }
+
+    /// Choose the clock used for timestamps and `start_record_now`, defaults to `RRInstantClock`.
+    ///
+    /// Use `RRSimulatedClock` to record in simulation time, or `RRTscClock` on x86_64 for cheaper real time
+    pub fn clock(mut self, clock: impl RecorderClock) -> Self {
+        self.clock = Box::new(clock);
+        if self.wall_epoch.is_some() {
+            self = self.wall_clock_timestamps();
+        }
+        self
+    }
+
+    /// Choose what happens to new events once the history buffer is full, defaults to `RROverflowPolicy::DropNewest`
+    pub fn overflow_policy(mut self, policy: RROverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// How many events the overflow policy has had to deal with so far, all zeros means the buffer never filled up
+    pub fn overflow_counts(&self) -> RROverflowCounts {
+        self.overflow_counters.load()
+    }
+
+    /// Starts the recorder paused, so nothing is recorded until `resume` is called
+    pub fn start_paused(self) -> Self {
+        self.pause();
+        self
+    }
+
+    /// Stops recording until `resume` is called, can be called from any thread.
+    ///
+    /// While paused, `add` and `start_record` on group handles do nothing beyond checking this flag, and records
+    /// started on shared groups are not saved. A record that was already started is still saved when the next one
+    /// starts, but readings added to it after the pause are ignored
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Starts recording again after `pause`. Handles pick it up with their next `start_record`
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// False while paused
+    #[inline(always)]
+    pub fn is_recording(&self) -> bool {
+        !self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Counts of saved, dropped, sampled out and overwritten data, use `RRStats::is_lossy` to check whether the history is complete
+    pub fn stats(&self) -> RRStats {
+        // every group handle's scope counts into the recorder's own counters
+        self.counters.load(
+            self.latest_readings.overwritten_count(),
+            self.overflow_counters.load(),
+        )
+    }
+
     pub fn add_group<SampleRate: ValidRapidRecorderNamedUsize>(
         &self,
         group: RapidRecorderGroup<SampleRate, IterationIndex>,
     ) -> RapidRecorderGroupHandle<'_, SampleRate, ReadingName, IterationIndex> {
         RapidRecorderGroupHandle::new(group, self)
     }
+
+    /// Like `add_group`, but the handle can be shared between threads that each start and save their own records,
+    /// see `RapidRecorderSharedGroupHandle`
+    pub fn add_shared_group<SampleRate: ValidRapidRecorderNamedUsize>(
+        &self,
+        group: RapidRecorderGroup<SampleRate, IterationIndex>,
+    ) -> RapidRecorderSharedGroupHandle<'_, SampleRate, ReadingName, IterationIndex> {
+        RapidRecorderSharedGroupHandle::new(group, self)
+    }
     /// Get a reference to the raw ArrayQueue buffer containing all recorded events. These events are NOT SORTED, either temporally or by index value. This is the fastest way to access the data, but it is up to the user to sort and filter it as needed.
     /// There are commonly duplicate events, you will have to handle that yourself
     pub fn raw_history(&self) -> &ArrayQueue<RawRREvent> {
@@ -90,7 +322,17 @@ impl<IterationIndex: ValidRapidRecorderNamedUsize, ReadingName: ValidRapidRecord
 
     /// a bit more expensive than just getting the raw history and popping yourself, but the event returned by this has usizes converted back to the enum types for easier reading and matching
     pub fn convenient_pop(&self) -> Option<RREvent<ReadingName, IterationIndex>> {
-        self.buffer.pop().map(|e| e.to_rr_event())
+        self.pop_raw().map(|e| {
+            let rr_event = e.to_rr_event();
+            self.recycle(e);
+            rr_event
+        })
+    }
+
+    // every consumer pops through here, so none of them mistakes a buffer emptied by `history_view` for the end
+    fn pop_raw(&self) -> Option<RawRREvent> {
+        let _shared = self.view_lock.read();
+        self.buffer.pop()
     }
     /// Returns a map of iteration indices to events, sorted by event ID within each iteration.
     ///
@@ -106,23 +348,10 @@ impl<IterationIndex: ValidRapidRecorderNamedUsize, ReadingName: ValidRapidRecord
     pub fn sorted_history(
         &self,
     ) -> HashMap<IterationIndex, Vec<RREvent<ReadingName, IterationIndex>>> {
-        let mut organized: HashMap<IterationIndex, Vec<RREvent<ReadingName, IterationIndex>>> =
-            HashMap::new();
-
-        while let Some(event) = self.buffer.pop() {
-            let rr_event = event.to_rr_event();
-            organized
-                .entry(rr_event.iteration_index)
-                .or_default()
-                .push(rr_event);
-        }
-
-        // Sort events by ID within each iteration
-        for events in organized.values_mut() {
-            events.sort_by_key(|e| e.id);
-        }
-
-        organized
+        organize_history(
+            std::iter::from_fn(|| self.convenient_pop()),
+            RRDuplicateEventIdHandling::KeepBoth,
+        )
     }
 
     /// Returns a map of iteration indices to events with control over how duplicate event IDs are handled.
@@ -144,53 +373,282 @@ impl<IterationIndex: ValidRapidRecorderNamedUsize, ReadingName: ValidRapidRecord
         &self,
         duplicate_event_id_handling: RRDuplicateEventIdHandling,
     ) -> HashMap<IterationIndex, Vec<RREvent<ReadingName, IterationIndex>>> {
-        let mut organized: HashMap<IterationIndex, Vec<RREvent<ReadingName, IterationIndex>>> =
-            HashMap::new();
-
-        while let Some(event) = self.buffer.pop() {
-            let rr_event = event.to_rr_event();
-            organized
-                .entry(rr_event.iteration_index)
-                .or_default()
-                .push(rr_event);
-        }
+        organize_history(
+            std::iter::from_fn(|| self.convenient_pop()),
+            duplicate_event_id_handling,
+        )
+    }
 
-        for events in organized.values_mut() {
-            // Handle duplicate event IDs according to the specified strategy
-            match duplicate_event_id_handling {
-                RRDuplicateEventIdHandling::KeepOnlyFirst => {
-                    let mut seen_ids = std::collections::HashSet::new();
-                    events.retain(|e| seen_ids.insert(e.id));
-                }
-                RRDuplicateEventIdHandling::KeepOnlyLast => {
-                    let mut seen_ids = std::collections::HashSet::new();
-                    events.reverse();
-                    events.retain(|e| seen_ids.insert(e.id));
-                    events.reverse();
-                }
-                RRDuplicateEventIdHandling::KeepBoth => {
-                    // Keep all events, no filtering needed
+    /// Like `sorted_history_with_duplicate_handling` but partitioned by group name first, so groups sharing an index type
+    /// stay apart and duplicate handling only compares record IDs within a group. Pick one group with
+    /// `.remove(&Some("substep".into()))`, unnamed groups are under `None`
+    pub fn sorted_history_by_group(
+        &self,
+        duplicate_event_id_handling: RRDuplicateEventIdHandling,
+    ) -> RRGroupedHistory<ReadingName, IterationIndex> {
+        organize_history_by_group(
+            std::iter::from_fn(|| self.convenient_pop()),
+            duplicate_event_id_handling,
+        )
+    }
+
+    /// Slices the history by the record ID at one level of the nested index path, see `RapidRecorderGroupHandle::add_child_group`.
+    ///
+    /// `sorted_history_by_level(DefaultIndexDimmension::Step, ..)[&Some(17)]` holds everything recorded during step 17,
+    /// including the step's own event, organized by index type. Duplicate handling only compares record IDs within a slice,
+    /// so per-cell IDs that restart every step are kept. Events not nested under `level` are under `None`
+    pub fn sorted_history_by_level(
+        &self,
+        level: IterationIndex,
+        duplicate_event_id_handling: RRDuplicateEventIdHandling,
+    ) -> RRLevelHistory<ReadingName, IterationIndex> {
+        organize_history_by_level(
+            std::iter::from_fn(|| self.convenient_pop()),
+            level,
+            duplicate_event_id_handling,
+        )
+    }
+
+    /// Returns a copy of every event currently in the history, in buffer order, WITHOUT consuming them.
+    ///
+    /// Use this to inspect history mid-run (for example from a UI thread) while leaving everything in place
+    /// for the final analysis. It costs a full pass over the buffer, so don't call it in a hot loop.
+    /// Saves are held off while the view is taken, so it sees a consistent history and every event is put back in order.
+    /// A save that arrives meanwhile waits for the view, so recording threads can stall for one pass over the buffer.
+    /// Saves blocked on a full buffer under `RROverflowPolicy::Block` don't hold the view up.
+    /// Events popped directly from `raw_history` while the view is being taken may be missing from it.
+    pub fn history_view(&self) -> Vec<RREvent<ReadingName, IterationIndex>> {
+        let mut view = Vec::new();
+        self.pass_over_history(|event| {
+            view.push(event.to_rr_event());
+            true
+        });
+        view
+    }
+
+    /// Pops every event in the buffer and puts back the ones `keep` returns true for, recycling the rest.
+    /// See `history_view` for how this interacts with concurrent saves
+    fn pass_over_history(&self, mut keep: impl FnMut(&RawRREvent) -> bool) {
+        // with saves held off, only events pushed straight into `raw_history` can take the room needed to put events back
+        self.save_gate.close();
+        let mut overflow = Vec::new();
+        {
+            // keeps `convenient_pop` and other views from seeing the buffer while it is emptied
+            let _exclusive = self.view_lock.write();
+            let mut raw = Vec::with_capacity(self.buffer.len());
+            while let Some(event) = self.buffer.pop() {
+                raw.push(event);
+            }
+            for event in raw {
+                if !keep(&event) {
+                    self.recycle(event);
+                } else if let Err(event) = self.buffer.push(event) {
+                    overflow.push(event);
                 }
             }
+        }
+        self.save_gate.open();
+        // outside the lock, `Block` waits for a consumer and consumers need the lock to pop
+        for event in overflow {
+            // already counted as saved the first time round
+            self.handle_overflow(event, &mut self.save_gate.enter());
+        }
+    }
+
+    /// The non-consuming version of `sorted_history_with_duplicate_handling`, see `history_view`
+    pub fn sorted_history_view(
+        &self,
+        duplicate_event_id_handling: RRDuplicateEventIdHandling,
+    ) -> HashMap<IterationIndex, Vec<RREvent<ReadingName, IterationIndex>>> {
+        organize_history(self.history_view(), duplicate_event_id_handling)
+    }
 
-            // Sort events by ID
-            events.sort_by_key(|e| e.id);
+    /// The non-consuming version of `sorted_history_by_group`, see `history_view`
+    pub fn sorted_history_view_by_group(
+        &self,
+        duplicate_event_id_handling: RRDuplicateEventIdHandling,
+    ) -> RRGroupedHistory<ReadingName, IterationIndex> {
+        organize_history_by_group(self.history_view(), duplicate_event_id_handling)
+    }
+
+    /// The non-consuming version of `sorted_history_by_level`, see `history_view`
+    pub fn sorted_history_view_by_level(
+        &self,
+        level: IterationIndex,
+        duplicate_event_id_handling: RRDuplicateEventIdHandling,
+    ) -> RRLevelHistory<ReadingName, IterationIndex> {
+        organize_history_by_level(self.history_view(), level, duplicate_event_id_handling)
+    }
+    /// Drains the history into `writer` using the binary recording format, see the `recording` module.
+    /// Load it again, even in another process, with `RecordingReader`. Returns the number of events written.
+    ///
+    /// Like `sorted_history` this consumes the events, wrap the writer in a `std::io::BufWriter` when writing to a file.
+    pub fn save_recording<W: Write>(&self, writer: W) -> io::Result<usize> {
+        let mut recording = RecordingWriter::new::<IterationIndex, ReadingName>(writer)?;
+        let mut written = 0;
+        while let Some(event) = self.pop_raw() {
+            recording.write_event(&event)?;
+            self.recycle(event);
+            written += 1;
         }
+        recording.finish()?;
+        Ok(written)
+    }
+
+    /// Drains the history into `writer` as CSV, one row per record. Returns the number of rows written.
+    ///
+    /// Columns are `index_type`, `record_id` and then one column per `ReadingName` variant, headed by its `Debug` name.
+    /// Rows are grouped by group name (unnamed first), then index type, and sorted by record ID. `options` controls what goes
+    /// in the cell of a reading that did not change during a record, how duplicate record IDs are handled
+    /// (see `sorted_history_by_group`), and whether to add a group column or export a single group.
+    /// When exporting a single group only its events are drained, other groups' events stay in the history.
+    pub fn export_csv<W: Write>(&self, writer: W, options: RRCsvOptions) -> io::Result<usize> {
+        let history = match &options.only_group {
+            Some(only) => {
+                let mut events = Vec::new();
+                self.pass_over_history(|event| {
+                    if event.group.as_ref() != Some(only) {
+                        return true;
+                    }
+                    events.push(event.to_rr_event());
+                    false
+                });
+                organize_history_by_group(events, options.duplicate_event_id_handling)
+            }
+            None => self.sorted_history_by_group(options.duplicate_event_id_handling),
+        };
+        write_csv(history, writer, options)
+    }
 
-        organized
+    /// Sets a reading in the recorder's shared scope, which only `_save_event` saves.
+    /// Group handles each have their own scope, so this never shows up in their records
+    pub fn _add_reading(&self, variable_name: usize, value: RRValue) {
+        self.latest_readings.set_typed_value(variable_name, value);
     }
-    pub fn _add_reading(&self, variable_name: usize, value: f64) {
-        self.latest_readings.set_value(variable_name, value);
+    /// The current time of the recorder's clock, what `RRTimestamps` are measured in
+    #[inline(always)]
+    pub fn now(&self) -> Duration {
+        self.clock.now()
     }
+
+    /// Saves the current readings as an event that started and ended right now, see `_save_event_started`
     #[inline(always)]
     pub fn _save_event(&self, index_type: usize, id: usize) {
-        let (readings, changed): (Vec<f64>, Vec<bool>) = self.latest_readings.snapshot();
-        let event = RawRREvent {
+        let now = self.now();
+        self._save_event_started(index_type, id, now);
+    }
+    /// Saves the current readings as an event for a record that began at `started` (as returned by `now`)
+    #[inline(always)]
+    pub fn _save_event_started(&self, index_type: usize, id: usize, started: Duration) {
+        self.save_scope(&self.latest_readings, index_type, id, started, None, &[]);
+    }
+
+    /// An empty set of readings with this recorder's reading modes, see `LatestReadingHolder::new_scope`
+    pub(crate) fn new_reading_scope(&self) -> LatestReadingHolder {
+        self.latest_readings.new_scope()
+    }
+
+    /// Snapshots `scope` into an event and pushes it into the history
+    #[inline(always)]
+    pub(crate) fn save_scope(
+        &self,
+        scope: &LatestReadingHolder,
+        index_type: usize,
+        id: usize,
+        started: Duration,
+        group: Option<&Arc<str>>,
+        parents: &[(usize, usize)],
+    ) {
+        let event = self.snapshot_event(scope, index_type, id, started, group, parents);
+        self.push_event(event);
+    }
+
+    /// Snapshots `scope` into an event without saving it, resetting the scope for the next record
+    #[inline(always)]
+    pub(crate) fn snapshot_event(
+        &self,
+        scope: &LatestReadingHolder,
+        index_type: usize,
+        id: usize,
+        started: Duration,
+        group: Option<&Arc<str>>,
+        parents: &[(usize, usize)],
+    ) -> RawRREvent {
+        let saved = self.now();
+        let timestamps = RRTimestamps {
+            started,
+            saved,
+            started_wall: self.wall_epoch.map(|epoch| epoch + started),
+            saved_wall: self.wall_epoch.map(|epoch| epoch + saved),
+        };
+        let readings = match (&self.event_pool, self.event_encoding) {
+            (Some(pool), encoding) => pool.snapshot(scope, encoding),
+            (None, RREventEncoding::Dense) => {
+                let (values, changed) = scope.snapshot();
+                RawRRReadings::Dense { values, changed }
+            }
+            (None, RREventEncoding::Sparse) => RawRRReadings::Sparse(scope.sparse_snapshot()),
+        };
+        RawRREvent {
             record_id: id,
             id_type: index_type,
             readings,
-            changed,
-        };
-        let _ = self.buffer.push(event);
+            histograms: scope.histogram_snapshot(),
+            moments: scope.moments_snapshot(),
+            timestamps,
+            group: group.cloned(),
+            parents: parents.to_vec(),
+        }
+    }
+
+    /// Pushes an event from `snapshot_event` into the history, applying the overflow policy if it is full
+    #[inline(always)]
+    pub(crate) fn push_event(&self, event: RawRREvent) {
+        let mut pass = self.save_gate.enter();
+        match self.buffer.push(event) {
+            Ok(()) => self.counters.count_saved(),
+            Err(event) => {
+                if self.handle_overflow(event, &mut pass) {
+                    self.counters.count_saved();
+                }
+            }
+        }
+    }
+    #[inline(always)]
+    pub(crate) fn count_sampled_out(&self) {
+        self.counters.count_sampled_out();
+    }
+
+    /// Applies the overflow policy to an event that didn't fit, returns whether it ended up in the buffer after all
+    #[cold]
+    fn handle_overflow(&self, mut event: RawRREvent, pass: &mut SavePass) -> bool {
+        match &self.overflow_policy {
+            RROverflowPolicy::DropNewest => {
+                self.overflow_counters.count(&self.overflow_policy);
+                self.recycle(event);
+                false
+            }
+            RROverflowPolicy::OverwriteOldest => {
+                // a consumer may have popped in the meantime, only count it if something was really displaced
+                if let Some(displaced) = self.buffer.force_push(event) {
+                    self.overflow_counters.count(&self.overflow_policy);
+                    self.recycle(displaced);
+                }
+                true
+            }
+            RROverflowPolicy::Block => {
+                self.overflow_counters.count(&self.overflow_policy);
+                while let Err(rejected) = self.buffer.push(event) {
+                    event = rejected;
+                    pass.step_aside();
+                }
+                true
+            }
+            RROverflowPolicy::Callback(callback) => {
+                self.overflow_counters.count(&self.overflow_policy);
+                callback(event);
+                false
+            }
+        }
     }
 }
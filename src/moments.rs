@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::latest_reading_holder::{ReadingSlot, slot};
+
+/// Count, mean and spread of a `MeanVariance` reading for one record, see `RREvent::moments`.
+///
+/// Holds the same `(count, mean, m2)` state as Welford's algorithm, so records can be combined with `merge`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RRMoments {
+    pub count: u64,
+    pub mean: f64,
+    /// Sum of squared differences from the mean
+    pub m2: f64,
+}
+
+impl RRMoments {
+    /// Population variance, 0 for a single value
+    pub fn variance(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.m2 / self.count as f64
+    }
+
+    /// Variance with Bessel's correction, NaN for fewer than two values
+    pub fn sample_variance(&self) -> f64 {
+        if self.count < 2 {
+            return f64::NAN;
+        }
+        self.m2 / (self.count - 1) as f64
+    }
+
+    /// Population standard deviation
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Combines the moments of two sets of values, e.g. to get the spread over several records
+    pub fn merge(&self, other: &RRMoments) -> RRMoments {
+        if self.count == 0 {
+            return *other;
+        }
+        if other.count == 0 {
+            return *self;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        RRMoments {
+            count,
+            mean: self.mean + delta * other.count as f64 / count as f64,
+            m2: self.m2
+                + other.m2
+                + delta * delta * self.count as f64 * other.count as f64 / count as f64,
+        }
+    }
+}
+
+/// Lock-free accumulator behind a `MeanVariance` reading.
+///
+/// Welford's update touches count, mean and m2 together, which can't be done with single word atomics.
+/// Instead every value is shifted by the first value of the record and the shifted sum and sum of squares are
+/// accumulated independently, which is just as stable as long as the first value is representative of the rest.
+pub(crate) struct AtomicMoments {
+    /// Bits of the first value of the record, NaN until one is added
+    shift: ReadingSlot<AtomicU64>,
+    count: ReadingSlot<AtomicU64>,
+    sum: ReadingSlot<AtomicU64>,
+    sum_sq: ReadingSlot<AtomicU64>,
+}
+
+const UNSET: u64 = 0x7ff8_0000_0000_0000; // f64::NAN.to_bits()
+
+impl AtomicMoments {
+    pub(crate) fn new() -> Self {
+        Self {
+            shift: slot(AtomicU64::new(UNSET)),
+            count: slot(AtomicU64::new(0)),
+            sum: slot(AtomicU64::new(0.0f64.to_bits())),
+            sum_sq: slot(AtomicU64::new(0.0f64.to_bits())),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn record(&self, value: f64) {
+        let mut shift = self.shift.load(Ordering::Relaxed);
+        if shift == UNSET {
+            // whoever gets here first decides the shift for everyone else
+            shift = match self.shift.compare_exchange(
+                UNSET,
+                value.to_bits(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => value.to_bits(),
+                Err(winner) => winner,
+            };
+        }
+        let delta = value - f64::from_bits(shift);
+        add_f64(&self.sum, delta);
+        add_f64(&self.sum_sq, delta * delta);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Takes the moments and resets for the next record, `None` if nothing was added
+    pub(crate) fn take(&self) -> Option<RRMoments> {
+        let count = self.count.swap(0, Ordering::Relaxed);
+        let sum = f64::from_bits(self.sum.swap(0.0f64.to_bits(), Ordering::Relaxed));
+        let sum_sq = f64::from_bits(self.sum_sq.swap(0.0f64.to_bits(), Ordering::Relaxed));
+        let shift = f64::from_bits(self.shift.swap(UNSET, Ordering::Relaxed));
+        if count == 0 {
+            return None;
+        }
+        let n = count as f64;
+        Some(RRMoments {
+            count,
+            mean: shift + sum / n,
+            // rounding can leave a tiny negative when every value is the same
+            m2: (sum_sq - sum * sum / n).max(0.0),
+        })
+    }
+}
+
+#[inline(always)]
+fn add_f64(target: &AtomicU64, value: f64) {
+    let _ = target.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        Some((f64::from_bits(bits) + value).to_bits())
+    });
+}
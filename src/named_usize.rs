@@ -17,6 +17,9 @@ pub trait ValidRapidRecorderNamedUsize:
 
 /// Implements the necessary traits for an enum to be used with RapidRecorder.
 ///
+/// `Debug` is not implemented by the macro, derive it on your enum. Its output is used as the variant name
+/// in saved recordings and exports, so the derived implementation is what you want.
+///
 /// # Safety
 ///
 /// The enum MUST be marked as so:
@@ -25,7 +28,7 @@ pub trait ValidRapidRecorderNamedUsize:
 /// use rapid_recorder::prelude::*;
 ///
 /// #[repr(u32)]
-/// #[derive(EnumIter)]
+/// #[derive(EnumIter, Debug)]
 /// enum MyEnum {
 ///     A,
 ///     B,
@@ -44,7 +47,7 @@ pub trait ValidRapidRecorderNamedUsize:
 /// use rapid_recorder::prelude::*;
 /// use strum_macros::EnumIter;
 /// #[repr(u32)]
-/// #[derive(strum_macros::EnumIter)]
+/// #[derive(strum_macros::EnumIter, Debug)]
 /// pub enum MyReadings {
 ///     Temperature,
 ///     Pressure,
@@ -93,12 +96,6 @@ macro_rules! impl_rapid_recorder_named_usize {
             }
         }
         impl Eq for $enum_type {}
-        impl std::fmt::Debug for $enum_type {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                // Use the default debug formatting
-                write!(f, "{:?}", *self)
-            }
-        }
         impl std::hash::Hash for $enum_type {
             fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
                 // Hash the discriminant safely without moving from self
@@ -146,11 +143,9 @@ pub fn validate_enum_for_recorder<T: ValidRapidRecorderNamedUsize>() -> Result<u
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use strum::IntoEnumIterator;
     use strum_macros::EnumIter;
     #[repr(u32)]
-    #[derive(EnumIter)]
+    #[derive(EnumIter, Debug)]
     enum TestEnum {
         A,
         B = 5,
@@ -159,11 +154,12 @@ mod tests {
     impl_rapid_recorder_named_usize!(TestEnum);
 
     #[test]
+    #[allow(clippy::clone_on_copy)]
     fn test_trait_implementations() {
         // Test Into<usize>
-        assert_eq!(<TestEnum as Into<usize>>::into(TestEnum::A), 0 as usize);
-        assert_eq!(<TestEnum as Into<usize>>::into(TestEnum::B), 5 as usize);
-        assert_eq!(<TestEnum as Into<usize>>::into(TestEnum::C), 6 as usize); // C comes after B which is 5
+        assert_eq!(<TestEnum as Into<usize>>::into(TestEnum::A), 0_usize);
+        assert_eq!(<TestEnum as Into<usize>>::into(TestEnum::B), 5_usize);
+        assert_eq!(<TestEnum as Into<usize>>::into(TestEnum::C), 6_usize); // C comes after B which is 5
 
         // Test From<usize>
         assert_eq!(TestEnum::from(0), TestEnum::A);
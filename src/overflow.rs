@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::event::RawRREvent;
+
+/// What the recorder should do with a new event when its history buffer is already full.
+///
+/// Only consulted on the slow path, a save into a buffer with free space never looks at the policy.
+#[derive(Default)]
+pub enum RROverflowPolicy {
+    /// Discard the event that was just recorded, keeping the older history intact (the default)
+    #[default]
+    DropNewest,
+    /// Ring-buffer semantics, the oldest event in the buffer is discarded to make room for the new one
+    OverwriteOldest,
+    /// Yield the thread until a consumer pops something from the buffer. Nothing is lost, but the recording thread stalls
+    /// until you drain the history, so only use this if another thread is consuming events while recording
+    Block,
+    /// Hand the event that did not fit to a user supplied callback, which can log it, persist it, or drop it
+    Callback(Box<dyn Fn(RawRREvent) + Send + Sync>),
+}
+
+/// How many events were affected by the overflow policy, see `RapidRecorder::overflow_counts`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RROverflowCounts {
+    /// Events thrown away by `RROverflowPolicy::DropNewest`
+    pub dropped_newest: usize,
+    /// Old events thrown away by `RROverflowPolicy::OverwriteOldest`
+    pub overwritten_oldest: usize,
+    /// Events that had to wait for free space under `RROverflowPolicy::Block` (none of these are lost)
+    pub blocked: usize,
+    /// Events passed to the `RROverflowPolicy::Callback` closure instead of the buffer
+    pub passed_to_callback: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct OverflowCounters {
+    dropped_newest: AtomicUsize,
+    overwritten_oldest: AtomicUsize,
+    blocked: AtomicUsize,
+    passed_to_callback: AtomicUsize,
+}
+
+impl OverflowCounters {
+    #[inline(always)]
+    pub(crate) fn count(&self, policy: &RROverflowPolicy) {
+        let counter = match policy {
+            RROverflowPolicy::DropNewest => &self.dropped_newest,
+            RROverflowPolicy::OverwriteOldest => &self.overwritten_oldest,
+            RROverflowPolicy::Block => &self.blocked,
+            RROverflowPolicy::Callback(_) => &self.passed_to_callback,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn load(&self) -> RROverflowCounts {
+        RROverflowCounts {
+            dropped_newest: self.dropped_newest.load(Ordering::Relaxed),
+            overwritten_oldest: self.overwritten_oldest.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            passed_to_callback: self.passed_to_callback.load(Ordering::Relaxed),
+        }
+    }
+}
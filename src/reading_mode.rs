@@ -0,0 +1,80 @@
+use crate::histogram::RRHistogramBuckets;
+use crate::value::{KIND_F64, KIND_I64, KIND_U64};
+
+/// How repeated `add` calls for the same reading within one record are combined, set with `RapidRecorder::reading_mode`.
+///
+/// Accumulators are reset when the record is saved, so each saved event holds the combination of that record's values only.
+/// `Sum`, `Min` and `Max` combine in the type the values were added with, `Count` saves a `u64` and `Mean` an `f64`.
+/// The first value added in a type the reading hasn't seen before replaces the accumulator instead of combining with it,
+/// so keep each accumulating reading to a single type.
+///
+/// `Histogram` and `MeanVariance` readings don't appear in the saved readings at all, they go to `RREvent::histograms`
+/// and `RREvent::moments`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RRReadingMode {
+    /// Keep whichever value was written last (the default), cheapest but parallel writes race
+    #[default]
+    Last,
+    Sum,
+    Min,
+    Max,
+    /// How many times the reading was added, the values themselves are ignored
+    Count,
+    /// Arithmetic mean of every value added, computed in `f64`
+    Mean,
+    /// Count how many values fall into each bucket, saved in `RREvent::histograms` instead of the readings
+    Histogram(RRHistogramBuckets),
+    /// Count, mean and variance of every value added, saved in `RREvent::moments` instead of the readings
+    MeanVariance,
+}
+
+impl RRReadingMode {
+    /// The bits an accumulator of the given value kind starts from, so combining with it is a no-op
+    #[inline(always)]
+    pub(crate) fn identity(self, kind: u8) -> u64 {
+        match (self, kind) {
+            (RRReadingMode::Min, KIND_F64) => f64::INFINITY.to_bits(),
+            (RRReadingMode::Min, KIND_I64) => i64::MAX as u64,
+            (RRReadingMode::Min, KIND_U64) => u64::MAX,
+            (RRReadingMode::Min, _) => 1,
+            (RRReadingMode::Max, KIND_F64) => f64::NEG_INFINITY.to_bits(),
+            (RRReadingMode::Max, KIND_I64) => i64::MIN as u64,
+            // zero bits are 0.0, 0 and false for every kind
+            _ => 0,
+        }
+    }
+
+    /// Combines two values of the same kind, both given as the bits stored in a reading slot
+    #[inline(always)]
+    pub(crate) fn combine(self, kind: u8, current: u64, value: u64) -> u64 {
+        match kind {
+            KIND_F64 => {
+                let (a, b) = (f64::from_bits(current), f64::from_bits(value));
+                match self {
+                    RRReadingMode::Min => a.min(b),
+                    RRReadingMode::Max => a.max(b),
+                    _ => a + b,
+                }
+                .to_bits()
+            }
+            KIND_I64 => {
+                let (a, b) = (current as i64, value as i64);
+                (match self {
+                    RRReadingMode::Min => a.min(b),
+                    RRReadingMode::Max => a.max(b),
+                    _ => a.wrapping_add(b),
+                }) as u64
+            }
+            KIND_U64 => match self {
+                RRReadingMode::Min => current.min(value),
+                RRReadingMode::Max => current.max(value),
+                _ => current.wrapping_add(value),
+            },
+            // bools: min is "all true", max and sum are "any true"
+            _ => match self {
+                RRReadingMode::Min => current & value,
+                _ => current | value,
+            },
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! A versioned binary file format for saving recordings and loading them in another process.
+//!
+//! Layout, all integers little endian:
+//! - magic `RAPIDREC`, then the format version as a `u16`
+//! - the `IndexDimmension` variants, then the `ReadingName` variants, each as a `u32` count followed by
+//!   `(discriminant: u32, name length: u16, UTF-8 name)` for every variant
+//! - any number of event records, each a `u8` tag and a `u32` payload length followed by the payload.
+//!   The payload of an event is, in order:
+//!   - `id_type: u32, record_id: u64, changed count: u32` followed by `(reading index: u32, kind: u8, bits: u64)` for
+//!     every changed reading, the exact typed value, see `RRValue`
+//!   - a `u32` histogram count followed by `(reading index: u32, layout: u8, min: f64, max: f64, bucket count: u32)`
+//!     and `bucket count + 2` `u64` counters for every histogram, see `RRHistogram`.
+//!     Layout 0 is `RRHistogramBuckets::Linear` and 1 is `RRHistogramBuckets::Log`
+//!   - a `u32` count followed by `(reading index: u32, count: u64, mean: f64, m2: f64)` for every `MeanVariance`
+//!     reading, see `RRMoments`
+//!   - the timestamps as `started: u64, saved: u64` nanoseconds since the recorder was created and a `u8` flag,
+//!     followed by `started: u64, saved: u64` nanoseconds since the Unix epoch when the flag is 1, see `RRTimestamps`
+//!   - the group name as a `u8` flag followed by `(name length: u16, UTF-8 name)` when the flag is 1
+//!   - a `u32` count followed by `(id_type: u32, record_id: u64)` for every parent group, outermost first
+//!
+//! New versions may only append fields to payloads or add new record tags. Readers skip payload bytes past the fields
+//! they know in recordings of a newer version, and records with tags they don't understand, so older builds can still
+//! read newer recordings.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+
+use crate::event::{RREvent, RawRREvent, RawRRReadings};
+use crate::histogram::{RRHistogram, RRHistogramBuckets};
+use crate::moments::RRMoments;
+use crate::named_usize::ValidRapidRecorderNamedUsize;
+use crate::sink::RREventSink;
+use crate::timestamps::RRTimestamps;
+use crate::value::RRValue;
+
+pub const RECORDING_MAGIC: &[u8; 8] = b"RAPIDREC";
+/// The format version this build writes, it reads every version
+pub const RECORDING_FORMAT_VERSION: u16 = 1;
+
+const EVENT_TAG: u8 = 1;
+
+/// One enum variant as stored in a recording header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RRVariantInfo {
+    pub discriminant: u32,
+    pub name: String,
+}
+
+fn variants_of<T: ValidRapidRecorderNamedUsize>() -> Vec<RRVariantInfo> {
+    T::iter()
+        .map(|variant| RRVariantInfo {
+            discriminant: <T as Into<usize>>::into(variant) as u32,
+            name: format!("{:?}", variant),
+        })
+        .collect()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes a recording header followed by events, see the module docs for the layout.
+///
+/// Can be passed to `RapidRecorder::drain_to` to stream events straight to disk while recording.
+pub struct RecordingWriter<W: Write> {
+    writer: W,
+    payload: Vec<u8>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    /// Writes the header describing both enum types, events can be written right after
+    pub fn new<
+        IndexDimmension: ValidRapidRecorderNamedUsize,
+        ReadingName: ValidRapidRecorderNamedUsize,
+    >(
+        mut writer: W,
+    ) -> io::Result<Self> {
+        writer.write_all(RECORDING_MAGIC)?;
+        writer.write_all(&RECORDING_FORMAT_VERSION.to_le_bytes())?;
+        for variants in [
+            variants_of::<IndexDimmension>(),
+            variants_of::<ReadingName>(),
+        ] {
+            writer.write_all(&(variants.len() as u32).to_le_bytes())?;
+            for variant in variants {
+                let name = variant.name.as_bytes();
+                let name_len = u16::try_from(name.len()).map_err(|_| {
+                    invalid_data(format!("variant name {} is too long", variant.name))
+                })?;
+                writer.write_all(&variant.discriminant.to_le_bytes())?;
+                writer.write_all(&name_len.to_le_bytes())?;
+                writer.write_all(name)?;
+            }
+        }
+        Ok(Self {
+            writer,
+            payload: Vec::new(),
+        })
+    }
+
+    pub fn write_event(&mut self, event: &RawRREvent) -> io::Result<()> {
+        self.payload.clear();
+        self.payload
+            .extend_from_slice(&(event.id_type as u32).to_le_bytes());
+        self.payload
+            .extend_from_slice(&(event.record_id as u64).to_le_bytes());
+        let count_at = self.payload.len();
+        self.payload.extend_from_slice(&0u32.to_le_bytes());
+        let mut count = 0u32;
+        for (index, value) in event.readings.changed() {
+            let (kind, bits) = value.to_bits();
+            self.payload
+                .extend_from_slice(&(index as u32).to_le_bytes());
+            self.payload.push(kind);
+            self.payload.extend_from_slice(&bits.to_le_bytes());
+            count += 1;
+        }
+        self.payload[count_at..count_at + 4].copy_from_slice(&count.to_le_bytes());
+        // histogram bucket counts
+        self.payload
+            .extend_from_slice(&(event.histograms.len() as u32).to_le_bytes());
+        for (index, histogram) in &event.histograms {
+            let (layout, min, max, count) = match histogram.buckets {
+                RRHistogramBuckets::Linear { min, max, count } => (0u8, min, max, count),
+                RRHistogramBuckets::Log { min, max, count } => (1u8, min, max, count),
+            };
+            self.payload
+                .extend_from_slice(&(*index as u32).to_le_bytes());
+            self.payload.push(layout);
+            self.payload.extend_from_slice(&min.to_le_bytes());
+            self.payload.extend_from_slice(&max.to_le_bytes());
+            self.payload
+                .extend_from_slice(&(count as u32).to_le_bytes());
+            for bucket in &histogram.counts {
+                self.payload.extend_from_slice(&bucket.to_le_bytes());
+            }
+        }
+        // mean and variance readings
+        self.payload
+            .extend_from_slice(&(event.moments.len() as u32).to_le_bytes());
+        for (index, moments) in &event.moments {
+            self.payload
+                .extend_from_slice(&(*index as u32).to_le_bytes());
+            self.payload.extend_from_slice(&moments.count.to_le_bytes());
+            self.payload.extend_from_slice(&moments.mean.to_le_bytes());
+            self.payload.extend_from_slice(&moments.m2.to_le_bytes());
+        }
+        // timestamps
+        let timestamps = &event.timestamps;
+        for time in [timestamps.started, timestamps.saved] {
+            self.payload
+                .extend_from_slice(&(time.as_nanos() as u64).to_le_bytes());
+        }
+        match (timestamps.started_wall, timestamps.saved_wall) {
+            (Some(started), Some(saved)) => {
+                self.payload.push(1);
+                for time in [started, saved] {
+                    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    self.payload
+                        .extend_from_slice(&(since_epoch.as_nanos() as u64).to_le_bytes());
+                }
+            }
+            _ => self.payload.push(0),
+        }
+        // group name
+        match &event.group {
+            Some(name) => {
+                let name_len = u16::try_from(name.len())
+                    .map_err(|_| invalid_data(format!("group name {} is too long", name)))?;
+                self.payload.push(1);
+                self.payload.extend_from_slice(&name_len.to_le_bytes());
+                self.payload.extend_from_slice(name.as_bytes());
+            }
+            None => self.payload.push(0),
+        }
+        // parent groups
+        self.payload
+            .extend_from_slice(&(event.parents.len() as u32).to_le_bytes());
+        for (id_type, record_id) in &event.parents {
+            self.payload
+                .extend_from_slice(&(*id_type as u32).to_le_bytes());
+            self.payload
+                .extend_from_slice(&(*record_id as u64).to_le_bytes());
+        }
+
+        self.writer.write_all(&[EVENT_TAG])?;
+        self.writer
+            .write_all(&(self.payload.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&self.payload)
+    }
+
+    /// Flushes and hands back the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write + Send + 'static> RREventSink for RecordingWriter<W> {
+    fn accept(&mut self, event: RawRREvent) -> Option<RawRREvent> {
+        if let Err(err) = self.write_event(&event) {
+            warn!("RecordingWriter failed to write event: {}", err);
+        }
+        Some(event)
+    }
+
+    fn flush(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            warn!("RecordingWriter failed to flush: {}", err);
+        }
+    }
+}
+
+/// An event read from a recording without the enum types, readings are keyed by variant name
+#[derive(Debug, Clone, PartialEq)]
+pub struct RRNamedEvent {
+    pub iteration_index: String,
+    pub id: usize,
+    pub values: HashMap<String, RRValue>,
+    pub histograms: HashMap<String, RRHistogram>,
+    pub moments: HashMap<String, RRMoments>,
+    pub timestamps: RRTimestamps,
+    pub group: Option<Arc<str>>,
+    /// The full index path by variant name, see `RREvent::path`
+    pub path: Vec<(String, usize)>,
+}
+
+/// Reads a recording written by `RecordingWriter` or `RapidRecorder::save_recording`
+pub struct RecordingReader<R: Read> {
+    reader: R,
+    version: u16,
+    index_variants: Vec<RRVariantInfo>,
+    reading_variants: Vec<RRVariantInfo>,
+}
+
+impl<R: Read> RecordingReader<R> {
+    /// Reads and checks the header, fails on anything that is not a recording
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != RECORDING_MAGIC {
+            return Err(invalid_data("not a rapid recorder recording".to_string()));
+        }
+        let version = read_u16(&mut reader)?;
+        if version == 0 {
+            return Err(invalid_data(
+                "recording format version 0 is invalid".to_string(),
+            ));
+        }
+        let index_variants = read_variants(&mut reader)?;
+        let reading_variants = read_variants(&mut reader)?;
+        Ok(Self {
+            reader,
+            version,
+            index_variants,
+            reading_variants,
+        })
+    }
+
+    /// The format version the recording was written with, may be newer than `RECORDING_FORMAT_VERSION`
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn index_variants(&self) -> &[RRVariantInfo] {
+        &self.index_variants
+    }
+
+    pub fn reading_variants(&self) -> &[RRVariantInfo] {
+        &self.reading_variants
+    }
+
+    /// Reads the next event as stored, `Ok(None)` at the end of the recording
+    pub fn next_raw(&mut self) -> io::Result<Option<RawRREvent>> {
+        loop {
+            let mut tag = [0u8; 1];
+            match self.reader.read_exact(&mut tag) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+            let payload_len = read_u32(&mut self.reader)? as u64;
+            // read as the bytes arrive rather than trusting the length with an allocation up front
+            let mut payload = Vec::new();
+            (&mut self.reader)
+                .take(payload_len)
+                .read_to_end(&mut payload)?;
+            if (payload.len() as u64) < payload_len {
+                return Err(invalid_data(
+                    "recording ends in the middle of an event".to_string(),
+                ));
+            }
+            if tag[0] != EVENT_TAG {
+                // written by a newer version, nothing we know how to interpret
+                continue;
+            }
+            return parse_event(&payload, self.version)
+                .map(Some)
+                .map_err(|err| {
+                    if err.kind() == io::ErrorKind::UnexpectedEof {
+                        invalid_data("event is shorter than its contents".to_string())
+                    } else {
+                        err
+                    }
+                });
+        }
+    }
+
+    /// Checks that the enum types match the header, then iterates over the events converted to them
+    pub fn events<
+        ReadingName: ValidRapidRecorderNamedUsize,
+        IndexDimmension: ValidRapidRecorderNamedUsize,
+    >(
+        self,
+    ) -> io::Result<impl Iterator<Item = io::Result<RREvent<ReadingName, IndexDimmension>>>> {
+        check_variants::<IndexDimmension>("IndexDimmension", &self.index_variants)?;
+        check_variants::<ReadingName>("ReadingName", &self.reading_variants)?;
+        let index_discriminants = discriminants(&self.index_variants);
+        let reading_discriminants = discriminants(&self.reading_variants);
+        let mut reader = self;
+        Ok(std::iter::from_fn(move || {
+            reader.next_raw().transpose().map(|event| {
+                let event = event?;
+                check_discriminants(&event, &index_discriminants, &reading_discriminants)?;
+                Ok(event.to_rr_event())
+            })
+        }))
+    }
+
+    /// Iterates over the events with readings keyed by the variant names in the header, for when the enum types aren't available
+    pub fn named_events(self) -> impl Iterator<Item = io::Result<RRNamedEvent>> {
+        let index_names = names_by_discriminant(&self.index_variants);
+        let reading_names = names_by_discriminant(&self.reading_variants);
+        let mut reader = self;
+        std::iter::from_fn(move || {
+            reader.next_raw().transpose().map(|event| {
+                let event = event?;
+                let name_of = |names: &HashMap<usize, String>, i: usize| {
+                    names.get(&i).cloned().ok_or_else(|| {
+                        invalid_data(format!("discriminant {} is not in the header", i))
+                    })
+                };
+                let mut values = HashMap::new();
+                for (index, value) in event.readings.changed() {
+                    values.insert(name_of(&reading_names, index)?, value);
+                }
+                let mut histograms = HashMap::new();
+                for (index, histogram) in event.histograms {
+                    histograms.insert(name_of(&reading_names, index)?, histogram);
+                }
+                let mut path = Vec::with_capacity(event.parents.len() + 1);
+                for (id_type, record_id) in event
+                    .parents
+                    .iter()
+                    .chain(std::iter::once(&(event.id_type, event.record_id)))
+                {
+                    path.push((name_of(&index_names, *id_type)?, *record_id));
+                }
+                let mut moments = HashMap::new();
+                for (index, m) in event.moments {
+                    moments.insert(name_of(&reading_names, index)?, m);
+                }
+                Ok(RRNamedEvent {
+                    iteration_index: name_of(&index_names, event.id_type)?,
+                    id: event.record_id,
+                    values,
+                    histograms,
+                    moments,
+                    timestamps: event.timestamps,
+                    group: event.group,
+                    path,
+                })
+            })
+        })
+    }
+}
+
+fn names_by_discriminant(variants: &[RRVariantInfo]) -> HashMap<usize, String> {
+    variants
+        .iter()
+        .map(|v| (v.discriminant as usize, v.name.clone()))
+        .collect()
+}
+
+fn discriminants(variants: &[RRVariantInfo]) -> HashSet<usize> {
+    variants.iter().map(|v| v.discriminant as usize).collect()
+}
+
+/// Fails if the event refers to a variant that is not in the header, converting it to the enum types would be undefined
+fn check_discriminants(
+    event: &RawRREvent,
+    index_discriminants: &HashSet<usize>,
+    reading_discriminants: &HashSet<usize>,
+) -> io::Result<()> {
+    let index_types = event
+        .parents
+        .iter()
+        .map(|(id_type, _)| *id_type)
+        .chain(std::iter::once(event.id_type));
+    let readings = event
+        .readings
+        .changed()
+        .map(|(index, _)| index)
+        .chain(event.histograms.iter().map(|(index, _)| *index))
+        .chain(event.moments.iter().map(|(index, _)| *index));
+    for (discriminant, known) in index_types
+        .map(|d| (d, index_discriminants))
+        .chain(readings.map(|d| (d, reading_discriminants)))
+    {
+        if !known.contains(&discriminant) {
+            return Err(invalid_data(format!(
+                "discriminant {} is not in the header",
+                discriminant
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_variants<T: ValidRapidRecorderNamedUsize>(
+    kind: &str,
+    stored: &[RRVariantInfo],
+) -> io::Result<()> {
+    let expected = variants_of::<T>();
+    if expected != stored {
+        return Err(invalid_data(format!(
+            "{} enum does not match the recording, recording has {:?} but the enum has {:?}",
+            kind, stored, expected
+        )));
+    }
+    Ok(())
+}
+
+fn parse_event(payload: &[u8], version: u16) -> io::Result<RawRREvent> {
+    let mut cursor = payload;
+    let id_type = read_u32(&mut cursor)? as usize;
+    let record_id = read_u64(&mut cursor)? as usize;
+    let count = read_u32(&mut cursor)? as usize;
+    // every pair takes 13 bytes, so a corrupt count can't make us allocate more than the payload holds
+    let mut pairs = Vec::with_capacity(count.min(cursor.len() / 13));
+    for _ in 0..count {
+        let index = read_u32(&mut cursor)? as usize;
+        let [kind] = read_array(&mut cursor)?;
+        let bits = read_u64(&mut cursor)?;
+        pairs.push((index, RRValue::from_bits(kind, bits)));
+    }
+    let mut histograms = Vec::new();
+    for _ in 0..read_u32(&mut cursor)? {
+        histograms.push(read_histogram(&mut cursor)?);
+    }
+    let mut moments = Vec::new();
+    for _ in 0..read_u32(&mut cursor)? {
+        let index = read_u32(&mut cursor)? as usize;
+        let count = read_u64(&mut cursor)?;
+        let mean = f64::from_le_bytes(read_array(&mut cursor)?);
+        let m2 = f64::from_le_bytes(read_array(&mut cursor)?);
+        moments.push((index, RRMoments { count, mean, m2 }));
+    }
+    let timestamps = read_timestamps(&mut cursor)?;
+    let mut group = None;
+    if let [1] = read_array(&mut cursor)? {
+        let name_len = read_u16(&mut cursor)? as usize;
+        let mut name = vec![0u8; name_len];
+        cursor.read_exact(&mut name)?;
+        let name = String::from_utf8(name)
+            .map_err(|_| invalid_data("group name is not valid UTF-8".to_string()))?;
+        group = Some(Arc::from(name));
+    }
+    let mut parents = Vec::new();
+    for _ in 0..read_u32(&mut cursor)? {
+        let id_type = read_u32(&mut cursor)? as usize;
+        let record_id = read_u64(&mut cursor)? as usize;
+        parents.push((id_type, record_id));
+    }
+    // newer versions append fields we don't know about, in a recording of our own version it can only be corruption
+    if version <= RECORDING_FORMAT_VERSION && !cursor.is_empty() {
+        return Err(invalid_data(format!(
+            "event has {} bytes left over",
+            cursor.len()
+        )));
+    }
+    Ok(RawRREvent {
+        readings: RawRRReadings::Sparse(pairs),
+        record_id,
+        id_type,
+        histograms,
+        moments,
+        timestamps,
+        group,
+        parents,
+    })
+}
+
+fn read_timestamps(cursor: &mut &[u8]) -> io::Result<RRTimestamps> {
+    let started = Duration::from_nanos(read_u64(cursor)?);
+    let saved = Duration::from_nanos(read_u64(cursor)?);
+    let [has_wall] = read_array(cursor)?;
+    let (started_wall, saved_wall) = if has_wall == 1 {
+        let started = UNIX_EPOCH + Duration::from_nanos(read_u64(cursor)?);
+        let saved = UNIX_EPOCH + Duration::from_nanos(read_u64(cursor)?);
+        (Some(started), Some(saved))
+    } else {
+        (None, None)
+    };
+    Ok(RRTimestamps {
+        started,
+        saved,
+        started_wall,
+        saved_wall,
+    })
+}
+
+/// Seconds since the Unix epoch, for exports
+pub(crate) fn unix_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
+
+fn read_histogram(cursor: &mut &[u8]) -> io::Result<(usize, RRHistogram)> {
+    let index = read_u32(cursor)? as usize;
+    let [layout] = read_array(cursor)?;
+    let min = f64::from_le_bytes(read_array(cursor)?);
+    let max = f64::from_le_bytes(read_array(cursor)?);
+    let count = read_u32(cursor)? as usize;
+    let buckets = match layout {
+        0 => RRHistogramBuckets::Linear { min, max, count },
+        1 => RRHistogramBuckets::Log { min, max, count },
+        other => {
+            return Err(invalid_data(format!(
+                "unknown histogram bucket layout {}",
+                other
+            )));
+        }
+    };
+    let counts = (0..count + 2)
+        .map(|_| read_u64(cursor))
+        .collect::<io::Result<_>>()?;
+    Ok((index, RRHistogram { buckets, counts }))
+}
+
+fn read_variants(reader: &mut impl Read) -> io::Result<Vec<RRVariantInfo>> {
+    let count = read_u32(reader)?;
+    (0..count)
+        .map(|_| {
+            let discriminant = read_u32(reader)?;
+            let name_len = read_u16(reader)? as usize;
+            let mut name = vec![0u8; name_len];
+            reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| invalid_data("variant name is not valid UTF-8".to_string()))?;
+            Ok(RRVariantInfo { discriminant, name })
+        })
+        .collect()
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    read_array(reader).map(u16::from_le_bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    read_array(reader).map(u32::from_le_bytes)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    read_array(reader).map(u64::from_le_bytes)
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use crate::event::RawRRReadings;
+use crate::named_usize::ValidRapidRecorderNamedUsize;
+use crate::value::RRValue;
+
+/// How far a reading has to move before a record is worth saving, set with `RapidRecorderGroup::deadband`.
+///
+/// Values are compared as `f64` against the reading's value in the last record the group saved, `true` is 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RRDeadband {
+    /// Save when a reading changed by more than this amount
+    Absolute(f64),
+    /// Save when a reading changed by more than this fraction of its last saved value, 0.01 is 1%
+    Relative(f64),
+}
+
+impl RRDeadband {
+    fn exceeded(self, last: f64, value: f64) -> bool {
+        let moved = (value - last).abs();
+        match self {
+            RRDeadband::Absolute(threshold) => moved > threshold,
+            RRDeadband::Relative(fraction) => moved > fraction * last.abs(),
+        }
+    }
+
+    /// Whether any changed reading moved past the deadband since `last_saved`, which is updated when it did.
+    /// Readings that were never saved before always count as moved, so do NaN values
+    pub(crate) fn check_and_update(
+        self,
+        readings: &RawRRReadings,
+        last_saved: &mut [Option<f64>],
+    ) -> bool {
+        let moved = readings.changed().any(|(index, value)| {
+            last_saved[index].is_none_or(|last| {
+                let value = value.as_f64();
+                // NaN never compares greater, but a reading going to or from NaN is a change worth keeping
+                value.is_nan() != last.is_nan() || self.exceeded(last, value)
+            })
+        });
+        if moved {
+            for (index, value) in readings.changed() {
+                last_saved[index] = Some(value.as_f64());
+            }
+        }
+        moved
+    }
+}
+
+/// Which record IDs a group saves beyond a fixed stride, set with `RapidRecorderGroup::sample_schedule`.
+///
+/// Schedules look at the record ID passed to `start_record`, minus the group's `sample_offset`.
+pub enum RRSampleSchedule {
+    /// 0 and every power of `base`: with base 2 that is 0, 1, 2, 4, 8, 16, ...
+    LogSpaced { base: usize },
+    /// Every ID below 10, then every 10th below 100, every 100th below 1000, and so on
+    Decades,
+    /// `length` consecutive IDs at the start of every `every` IDs, e.g. 0..5, 100..105, 200..205. An `every` of 0 saves nothing
+    Burst { length: usize, every: usize },
+    /// Any rule you like, called once per `start_record`. On a `RapidRecorderSharedGroupHandle` it is called from every
+    /// thread that starts records, possibly at the same time, so it must not assume calls come one at a time or in order
+    Predicate(Box<dyn Fn(usize) -> bool + Send + Sync>),
+}
+
+impl RRSampleSchedule {
+    /// Wraps a closure in `RRSampleSchedule::Predicate`
+    pub fn predicate(predicate: impl Fn(usize) -> bool + Send + Sync + 'static) -> Self {
+        RRSampleSchedule::Predicate(Box::new(predicate))
+    }
+
+    pub fn includes(&self, id: usize) -> bool {
+        match self {
+            RRSampleSchedule::LogSpaced { base } => {
+                if id <= 1 {
+                    return true;
+                }
+                let mut power = *base;
+                while power < id {
+                    match power.checked_mul(*base) {
+                        Some(next) if next > power => power = next,
+                        _ => return false,
+                    }
+                }
+                power == id
+            }
+            RRSampleSchedule::Decades => {
+                let mut step = 1;
+                while id / step >= 10 {
+                    step *= 10;
+                }
+                id.is_multiple_of(step)
+            }
+            RRSampleSchedule::Burst { length, every } => id
+                .checked_rem(*every)
+                .is_some_and(|offset| offset < *length),
+            RRSampleSchedule::Predicate(predicate) => predicate(id),
+        }
+    }
+}
+
+impl fmt::Debug for RRSampleSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RRSampleSchedule::LogSpaced { base } => {
+                f.debug_struct("LogSpaced").field("base", base).finish()
+            }
+            RRSampleSchedule::Decades => write!(f, "Decades"),
+            RRSampleSchedule::Burst { length, every } => f
+                .debug_struct("Burst")
+                .field("length", length)
+                .field("every", every)
+                .finish(),
+            RRSampleSchedule::Predicate(_) => write!(f, "Predicate(..)"),
+        }
+    }
+}
+
+/// Flight recorder mode for a group, set with `RapidRecorderGroup::trigger`.
+///
+/// The group keeps its last `before` records in a rolling window instead of saving them. When a record's `reading`
+/// satisfies the condition, the window, the triggering record and the next `after` records are committed to the history.
+/// A trigger firing during the `after` records extends them. Records that fall out of the window count as sampled out.
+pub struct RRTrigger<ReadingName: ValidRapidRecorderNamedUsize> {
+    pub reading: ReadingName,
+    pub condition: Box<dyn Fn(RRValue) -> bool + Send + Sync>,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl<ReadingName: ValidRapidRecorderNamedUsize> RRTrigger<ReadingName> {
+    /// Fires when `reading` changes during a record and `condition` returns true for its value,
+    /// e.g. `RRTrigger::new(Readings::Residual, |v| v.as_f64().is_nan())`. Keeps no records around it until
+    /// `before` and `after` are set
+    pub fn new(
+        reading: ReadingName,
+        condition: impl Fn(RRValue) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            reading,
+            condition: Box::new(condition),
+            before: 0,
+            after: 0,
+        }
+    }
+
+    /// How many records before the trigger to keep
+    pub fn before(mut self, records: usize) -> Self {
+        self.before = records;
+        self
+    }
+
+    /// How many records after the trigger to save
+    pub fn after(mut self, records: usize) -> Self {
+        self.after = records;
+        self
+    }
+}
+
+impl<ReadingName: ValidRapidRecorderNamedUsize> fmt::Debug for RRTrigger<ReadingName> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RRTrigger")
+            .field("reading", &self.reading)
+            .field("before", &self.before)
+            .field("after", &self.after)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An `RRTrigger` with its reading converted to a slot index, which is what groups keep since they don't know the
+/// reading type
+pub(crate) struct RawRRTrigger {
+    pub reading: usize,
+    // checked against the recorder's reading type when the group is added, see `RawRRTrigger::check_reading`
+    pub reading_type: &'static str,
+    pub condition: Box<dyn Fn(RRValue) -> bool + Send + Sync>,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl RawRRTrigger {
+    /// Panics unless the trigger was built on the recorder's `ReadingName`, a trigger on any other enum would never fire
+    pub(crate) fn check_reading<ReadingName: ValidRapidRecorderNamedUsize>(&self, capacity: usize) {
+        let recorder_type = std::any::type_name::<ReadingName>();
+        if self.reading_type != recorder_type {
+            panic!(
+                "RRTrigger watches a {} reading, but the recorder's readings are {}",
+                self.reading_type, recorder_type
+            );
+        }
+        if self.reading >= capacity {
+            panic!(
+                "RRTrigger watches reading {} but the recorder only has {} readings",
+                self.reading, capacity
+            );
+        }
+    }
+
+    pub(crate) fn fires(&self, readings: &RawRRReadings) -> bool {
+        readings
+            .changed()
+            .any(|(index, value)| index == self.reading && (self.condition)(value))
+    }
+}
+
+impl<ReadingName: ValidRapidRecorderNamedUsize> From<RRTrigger<ReadingName>> for RawRRTrigger {
+    fn from(trigger: RRTrigger<ReadingName>) -> Self {
+        Self {
+            reading: trigger.reading.into(),
+            reading_type: std::any::type_name::<ReadingName>(),
+            condition: trigger.condition,
+            before: trigger.before,
+            after: trigger.after,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crossbeam::utils::CachePadded;
+
+/// Lets `RapidRecorder::history_view` hold saves off for the length of its pass without a shared lock on the save path.
+///
+/// Every save counts itself in flight on a stripe picked by its thread, so threads saving at the same time don't
+/// contend on one counter. A view closes the gate and waits for the saves already through it, saves arriving
+/// while it is closed wait for the view to finish.
+pub(crate) struct SaveGate {
+    viewing: AtomicBool,
+    in_flight: Box<[CachePadded<AtomicUsize>]>,
+}
+
+/// A save that is through the gate, it leaves the gate when dropped
+pub(crate) struct SavePass<'a> {
+    gate: &'a SaveGate,
+    stripe: usize,
+}
+
+thread_local! {
+    static STRIPE: usize = {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    };
+}
+
+impl SaveGate {
+    pub(crate) fn new() -> Self {
+        let stripes = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            viewing: AtomicBool::new(false),
+            in_flight: (0..stripes)
+                .map(|_| CachePadded::new(AtomicUsize::new(0)))
+                .collect(),
+        }
+    }
+
+    /// Waits until no view is running, then lets a save through
+    #[inline(always)]
+    pub(crate) fn enter(&self) -> SavePass<'_> {
+        let stripe = STRIPE.with(|stripe| *stripe) % self.in_flight.len();
+        self.arrive(stripe);
+        SavePass { gate: self, stripe }
+    }
+
+    #[inline(always)]
+    fn arrive(&self, stripe: usize) {
+        loop {
+            // paired with the store and loads in `close`, either the view sees this save or the save sees the view
+            self.in_flight[stripe].fetch_add(1, Ordering::SeqCst);
+            if !self.viewing.load(Ordering::SeqCst) {
+                return;
+            }
+            self.in_flight[stripe].fetch_sub(1, Ordering::Release);
+            self.wait_for_view();
+        }
+    }
+
+    #[cold]
+    fn wait_for_view(&self) {
+        while self.viewing.load(Ordering::Acquire) {
+            std::thread::yield_now();
+        }
+    }
+
+    /// Stops new saves and waits for the ones already through the gate, one view at a time
+    pub(crate) fn close(&self) {
+        while self
+            .viewing
+            .compare_exchange_weak(false, true, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            std::thread::yield_now();
+        }
+        for stripe in self.in_flight.iter() {
+            while stripe.load(Ordering::SeqCst) != 0 {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    pub(crate) fn open(&self) {
+        self.viewing.store(false, Ordering::Release);
+    }
+}
+
+impl SavePass<'_> {
+    /// Leaves the gate while the save waits for room in the buffer, so a view isn't held up by a full buffer
+    #[cold]
+    pub(crate) fn step_aside(&mut self) {
+        self.gate.in_flight[self.stripe].fetch_sub(1, Ordering::Release);
+        std::thread::yield_now();
+        self.gate.arrive(self.stripe);
+    }
+}
+
+impl Drop for SavePass<'_> {
+    fn drop(&mut self) {
+        self.gate.in_flight[self.stripe].fetch_sub(1, Ordering::Release);
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::{
+    RapidRecorder, group::RapidRecorderGroup, group_handle::GroupState,
+    latest_reading_holder::LatestReadingHolder, named_usize::ValidRapidRecorderNamedUsize,
+    value::RRValue,
+};
+
+/// A group handle that any number of threads can start records on at the same time, created with
+/// `RapidRecorder::add_shared_group`.
+///
+/// Every `start_record` returns its own `RRSharedRecord` with its own readings, so e.g. request handlers in a server
+/// can each record their request without seeing each other's values. The record is saved when it is dropped.
+/// Sampling works as for `RapidRecorderGroupHandle`, except that nested child groups are not supported.
+pub struct RapidRecorderSharedGroupHandle<
+    'a,
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> {
+    state: GroupState<SamplingFrequency, IndexDimmension>,
+    recorder: &'a RapidRecorder<IndexDimmension, ReadingName>,
+    // readings of records that were saved, reused so starting a record doesn't allocate.
+    // They count overwritten readings straight into the recorder's stats, so nothing is lost by keeping them around
+    spare_readings: Mutex<Vec<LatestReadingHolder>>,
+}
+
+/// One record of a `RapidRecorderSharedGroupHandle`, saved when dropped.
+///
+/// Readings can be added from any thread that can see the record. If the group's sampling skipped this record,
+/// adding readings does nothing and nothing is saved.
+pub struct RRSharedRecord<
+    'h,
+    'a,
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> {
+    handle: &'h RapidRecorderSharedGroupHandle<'a, SamplingFrequency, ReadingName, IndexDimmension>,
+    id: usize,
+    started: Duration,
+    // None when the record is sampled out
+    readings: Option<LatestReadingHolder>,
+}
+
+impl<
+    'a,
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> RapidRecorderSharedGroupHandle<'a, SamplingFrequency, ReadingName, IndexDimmension>
+{
+    pub fn new(
+        group: RapidRecorderGroup<SamplingFrequency, IndexDimmension>,
+        recorder: &'a RapidRecorder<IndexDimmension, ReadingName>,
+    ) -> Self {
+        Self {
+            state: GroupState::new(group, recorder),
+            recorder,
+            spare_readings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Starts a record with the given unique_id, which is saved when the returned record is dropped.
+    /// Can be called from any number of threads at once, each record only sees the readings added to it.
+    /// Records started while the recorder is paused are never saved
+    pub fn start_record(
+        &self,
+        unique_id: usize,
+    ) -> RRSharedRecord<'_, 'a, SamplingFrequency, ReadingName, IndexDimmension> {
+        let readings = if !self.recorder.is_recording() {
+            None
+        } else if self.sample(unique_id) {
+            let spare = self.spare_readings.lock().pop();
+            Some(spare.unwrap_or_else(|| self.recorder.new_reading_scope()))
+        } else {
+            self.recorder.count_sampled_out();
+            None
+        };
+        RRSharedRecord {
+            handle: self,
+            id: unique_id,
+            started: self.recorder.now(),
+            readings,
+        }
+    }
+
+    fn sample(&self, unique_id: usize) -> bool {
+        if self.state.complex_sampling {
+            self.state.sample(self.recorder, unique_id)
+        } else {
+            unique_id.is_multiple_of(self.state.sample_rate)
+        }
+    }
+}
+
+impl<
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> RRSharedRecord<'_, '_, SamplingFrequency, ReadingName, IndexDimmension>
+{
+    /// Sets the reading for this record, see `RapidRecorderGroupHandle::add`
+    #[inline(always)]
+    pub fn add(&self, reading_name: ReadingName, value: impl Into<RRValue>) {
+        if let Some(readings) = &self.readings {
+            readings.set_typed_value(reading_name.into(), value.into());
+        }
+    }
+
+    /// The unique_id the record was started with
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Whether the record will be saved, false if the group's sampling skipped it
+    pub fn is_sampled(&self) -> bool {
+        self.readings.is_some()
+    }
+}
+
+impl<
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> Drop for RRSharedRecord<'_, '_, SamplingFrequency, ReadingName, IndexDimmension>
+{
+    fn drop(&mut self) {
+        let Some(readings) = self.readings.take() else {
+            return;
+        };
+        let handle = self.handle;
+        handle
+            .state
+            .save(handle.recorder, &readings, self.id, self.started, &[]);
+        handle.spare_readings.lock().push(readings);
+    }
+}
+
+impl<
+    SamplingFrequency: ValidRapidRecorderNamedUsize,
+    ReadingName: ValidRapidRecorderNamedUsize,
+    IndexDimmension: ValidRapidRecorderNamedUsize,
+> Drop for RapidRecorderSharedGroupHandle<'_, SamplingFrequency, ReadingName, IndexDimmension>
+{
+    fn drop(&mut self) {
+        self.state.retire(self.recorder);
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossbeam::queue::ArrayQueue;
+use log::warn;
+
+use crate::event::RawRREvent;
+use crate::event_pool::EventPool;
+
+/// Somewhere for the background drain thread to send events, see `RapidRecorder::drain_to`.
+///
+/// Implemented for closures, `std::sync::mpsc::Sender<RawRREvent>`, `RRWriterSink` and `RecordingWriter`.
+pub trait RREventSink: Send + 'static {
+    /// Called once for every event popped from the buffer, in buffer order.
+    /// Hand the event back when you are done with it so its storage can be reused by `RapidRecorder::preallocate_events`,
+    /// return `None` if you keep it
+    fn accept(&mut self, event: RawRREvent) -> Option<RawRREvent>;
+    /// Called after the final drain when the recorder is dropped
+    fn flush(&mut self) {}
+}
+
+/// Closures keep the event, so with `preallocate_events` every drained event is replaced by a new allocation
+impl<F: FnMut(RawRREvent) + Send + 'static> RREventSink for F {
+    fn accept(&mut self, event: RawRREvent) -> Option<RawRREvent> {
+        self(event);
+        None
+    }
+}
+
+/// Sends the event to the receiver, so with `preallocate_events` every drained event is replaced by a new allocation
+impl RREventSink for Sender<RawRREvent> {
+    fn accept(&mut self, event: RawRREvent) -> Option<RawRREvent> {
+        // the receiver hanging up just means nobody wants the rest, no reason to take the drain thread down
+        let _ = self.send(event);
+        None
+    }
+}
+
+/// Writes one line of text per event: `id_type record_id reading_index=value reading_index=value ...`,
+/// listing only the readings that changed during the record
+pub struct RRWriterSink<W: Write + Send + 'static> {
+    writer: W,
+}
+
+impl<W: Write + Send + 'static> RRWriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write + Send + 'static> RREventSink for RRWriterSink<W> {
+    fn accept(&mut self, event: RawRREvent) -> Option<RawRREvent> {
+        let mut line = format!("{} {}", event.id_type, event.record_id);
+        for (index, value) in event.readings.changed() {
+            line.push_str(&format!(" {}={}", index, value));
+        }
+        if let Err(err) = writeln!(self.writer, "{}", line) {
+            warn!("RRWriterSink failed to write event: {}", err);
+        }
+        Some(event)
+    }
+
+    fn flush(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            warn!("RRWriterSink failed to flush: {}", err);
+        }
+    }
+}
+
+/// The consumer thread started by `RapidRecorder::drain_to`, stopped and joined on drop after one last drain
+pub(crate) struct DrainThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DrainThread {
+    pub(crate) fn spawn(
+        buffer: Arc<ArrayQueue<RawRREvent>>,
+        pool: Option<Arc<EventPool>>,
+        mut sink: impl RREventSink,
+        poll_interval: Duration,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("rapid-recorder-drain".into())
+            .spawn(move || {
+                loop {
+                    // read the flag before draining so nothing saved before the stop is missed
+                    let stopping = thread_stop.load(Ordering::Acquire);
+                    while let Some(event) = buffer.pop() {
+                        if let Some(event) = sink.accept(event)
+                            && let Some(pool) = &pool
+                        {
+                            pool.give_back(event.readings);
+                        }
+                    }
+                    if stopping {
+                        break;
+                    }
+                    std::thread::park_timeout(poll_interval);
+                }
+                sink.flush();
+            })
+            .expect("failed to spawn rapid recorder drain thread");
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for DrainThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            if handle.join().is_err() {
+                warn!(
+                    "rapid recorder drain thread panicked, some events may not have reached the sink"
+                );
+            }
+        }
+    }
+}
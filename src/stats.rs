@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::overflow::RROverflowCounts;
+
+/// A snapshot of how complete a recording is, returned by `RapidRecorder::stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RRStats {
+    /// Events that made it into the history buffer, under `RROverflowPolicy::OverwriteOldest` some may since have been overwritten
+    pub saved: usize,
+    /// Events lost because the history buffer was full, either the new event or an overwritten old one
+    pub dropped: usize,
+    /// Records skipped by a group's sampling options or deadband, these were never meant to be saved
+    pub sampled_out: usize,
+    /// Readings replaced by a later `add` of the same reading name before any record captured them
+    pub overwritten_readings: usize,
+    /// The raw per-policy overflow counts, see `RROverflowCounts`
+    pub overflow: RROverflowCounts,
+}
+
+impl RRStats {
+    /// True if any recorded data was lost, either whole events or individual readings.
+    /// Sampled out records are intentional and do not count as loss.
+    pub fn is_lossy(&self) -> bool {
+        self.dropped > 0 || self.overwritten_readings > 0
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RecorderCounters {
+    saved: AtomicUsize,
+    sampled_out: AtomicUsize,
+}
+
+impl RecorderCounters {
+    #[inline(always)]
+    pub(crate) fn count_saved(&self) {
+        self.saved.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub(crate) fn count_sampled_out(&self) {
+        self.sampled_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn load(&self, overwritten_readings: usize, overflow: RROverflowCounts) -> RRStats {
+        RRStats {
+            saved: self.saved.load(Ordering::Relaxed),
+            dropped: overflow.dropped_newest + overflow.overwritten_oldest,
+            sampled_out: self.sampled_out.load(Ordering::Relaxed),
+            overwritten_readings,
+            overflow,
+        }
+    }
+}
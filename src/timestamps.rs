@@ -0,0 +1,23 @@
+use std::time::{Duration, SystemTime};
+
+/// When a record was started and saved, captured automatically by the recorder.
+///
+/// The monotonic times come from the recorder's clock (`RapidRecorder::clock`), by default measured from when the recorder
+/// was created. The wall clock times are only set when `RapidRecorder::wall_clock_timestamps` was used. They are derived
+/// from the monotonic times, so they never jump backwards during a recording even if the system clock is adjusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RRTimestamps {
+    /// When `start_record` began this record
+    pub started: Duration,
+    /// When the record was saved, i.e. the next `start_record`, `_save_record` or the handle being dropped
+    pub saved: Duration,
+    pub started_wall: Option<SystemTime>,
+    pub saved_wall: Option<SystemTime>,
+}
+
+impl RRTimestamps {
+    /// How long the record was open
+    pub fn duration(&self) -> Duration {
+        self.saved.saturating_sub(self.started)
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+
+/// A single reading value, keeping the type it was added with so integers above 2^53 and flags survive intact.
+///
+/// `RapidRecorderGroupHandle::add` accepts anything that converts into this, so `f64`, `i64`, `u64`, `bool`
+/// (and the smaller integer types) can all be recorded directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RRValue {
+    F64(f64),
+    I64(i64),
+    U64(u64),
+    Bool(bool),
+}
+
+pub(crate) const KIND_F64: u8 = 0;
+pub(crate) const KIND_I64: u8 = 1;
+pub(crate) const KIND_U64: u8 = 2;
+pub(crate) const KIND_BOOL: u8 = 3;
+
+impl RRValue {
+    /// Lossy conversion for code that only deals in floats, integers above 2^53 get rounded and `true` is `1.0`
+    pub fn as_f64(self) -> f64 {
+        match self {
+            RRValue::F64(v) => v,
+            RRValue::I64(v) => v as f64,
+            RRValue::U64(v) => v as f64,
+            RRValue::Bool(v) => v as u8 as f64,
+        }
+    }
+
+    /// Splits into the `(kind, bits)` pair stored in the lock-free reading slots
+    #[inline(always)]
+    pub(crate) fn to_bits(self) -> (u8, u64) {
+        match self {
+            RRValue::F64(v) => (KIND_F64, v.to_bits()),
+            RRValue::I64(v) => (KIND_I64, v as u64),
+            RRValue::U64(v) => (KIND_U64, v),
+            RRValue::Bool(v) => (KIND_BOOL, v as u64),
+        }
+    }
+
+    /// Inverse of `to_bits`, unknown kinds are read as `f64`
+    #[inline(always)]
+    pub(crate) fn from_bits(kind: u8, bits: u64) -> Self {
+        match kind {
+            KIND_I64 => RRValue::I64(bits as i64),
+            KIND_U64 => RRValue::U64(bits),
+            KIND_BOOL => RRValue::Bool(bits != 0),
+            _ => RRValue::F64(f64::from_bits(bits)),
+        }
+    }
+}
+
+impl Default for RRValue {
+    fn default() -> Self {
+        RRValue::F64(0.0)
+    }
+}
+
+impl fmt::Display for RRValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RRValue::F64(v) => write!(f, "{}", v),
+            RRValue::I64(v) => write!(f, "{}", v),
+            RRValue::U64(v) => write!(f, "{}", v),
+            RRValue::Bool(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+macro_rules! impl_rr_value_conversions {
+    ($variant:ident, $stored:ty, [$($small:ty),*]) => {
+        impl From<$stored> for RRValue {
+            fn from(value: $stored) -> Self {
+                RRValue::$variant(value)
+            }
+        }
+        impl TryFrom<RRValue> for $stored {
+            type Error = RRValue;
+            fn try_from(value: RRValue) -> Result<Self, RRValue> {
+                match value {
+                    RRValue::$variant(v) => Ok(v),
+                    other => Err(other),
+                }
+            }
+        }
+        $(
+            impl From<$small> for RRValue {
+                fn from(value: $small) -> Self {
+                    RRValue::$variant(value.into())
+                }
+            }
+            impl TryFrom<RRValue> for $small {
+                type Error = RRValue;
+                fn try_from(value: RRValue) -> Result<Self, RRValue> {
+                    match value {
+                        RRValue::$variant(v) => <$small>::try_from(v).map_err(|_| value),
+                        other => Err(other),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_rr_value_conversions!(I64, i64, [i32, i16, i8]);
+impl_rr_value_conversions!(U64, u64, [u32, u16, u8]);
+impl_rr_value_conversions!(Bool, bool, []);
+
+impl From<f64> for RRValue {
+    fn from(value: f64) -> Self {
+        RRValue::F64(value)
+    }
+}
+impl From<f32> for RRValue {
+    fn from(value: f32) -> Self {
+        RRValue::F64(value.into())
+    }
+}
+impl TryFrom<RRValue> for f64 {
+    type Error = RRValue;
+    fn try_from(value: RRValue) -> Result<Self, RRValue> {
+        match value {
+            RRValue::F64(v) => Ok(v),
+            other => Err(other),
+        }
+    }
+}
+impl From<usize> for RRValue {
+    fn from(value: usize) -> Self {
+        RRValue::U64(value as u64)
+    }
+}
+impl TryFrom<RRValue> for usize {
+    type Error = RRValue;
+    fn try_from(value: RRValue) -> Result<Self, RRValue> {
+        match value {
+            RRValue::U64(v) => usize::try_from(v).map_err(|_| value),
+            other => Err(other),
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use rapid_recorder::{RRDuplicateEventIdHandling, prelude::*};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use strum_macros::EnumIter;
 
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 
 enum TestReadings {
     Reading0,
@@ -92,3 +94,872 @@ fn test_sampling_frequency() {
     // We should have 2 events from EveryTen group (0 and 10)
     assert_eq!(ten_count, 2);
 }
+
+#[test]
+fn test_overflow_policies() {
+    let record_five = |recorder: &RapidRecorder<DefaultIndexDimmension, TestReadings>| {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        for i in 0..5 {
+            group.start_record(i);
+            group.add(TestReadings::Reading0, i as f64);
+        }
+    };
+
+    // DropNewest keeps the first records
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(3, 3);
+    record_five(&recorder);
+    assert_eq!(recorder.overflow_counts().dropped_newest, 2);
+    assert_eq!(recorder.convenient_pop().unwrap().id, 0);
+
+    // OverwriteOldest keeps the last records
+    let recorder: RapidRecorder<_, TestReadings> =
+        RapidRecorder::new(3, 3).overflow_policy(RROverflowPolicy::OverwriteOldest);
+    record_five(&recorder);
+    assert_eq!(recorder.overflow_counts().overwritten_oldest, 2);
+    let ids: Vec<usize> = std::iter::from_fn(|| recorder.convenient_pop())
+        .map(|e| e.id)
+        .collect();
+    assert_eq!(ids, vec![2, 3, 4]);
+
+    // Callback receives every event that did not fit
+    let overflowed = Arc::new(Mutex::new(Vec::new()));
+    let sink = overflowed.clone();
+    let recorder: RapidRecorder<_, TestReadings> =
+        RapidRecorder::new(3, 3).overflow_policy(RROverflowPolicy::Callback(Box::new(move |e| {
+            sink.lock().unwrap().push(e.record_id)
+        })));
+    record_five(&recorder);
+    assert_eq!(recorder.overflow_counts().passed_to_callback, 2);
+    assert_eq!(*overflowed.lock().unwrap(), vec![3, 4]);
+}
+
+#[test]
+fn test_block_overflow_policy_waits_for_consumer() {
+    let recorder: RapidRecorder<_, TestReadings> =
+        RapidRecorder::new(2, 3).overflow_policy(RROverflowPolicy::Block);
+    let mut received = Vec::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut group = recorder.add_group(RapidRecorderGroup::default());
+            for i in 0..10 {
+                group.start_record(i);
+                group.add(TestReadings::Reading1, 1.0);
+            }
+        });
+        while received.len() < 10 {
+            match recorder.convenient_pop() {
+                Some(event) => received.push(event.id),
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    });
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+    assert!(recorder.overflow_counts().blocked > 0);
+}
+
+#[test]
+fn test_stats() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(5, 3);
+    {
+        let mut group = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryTen)
+                .index_type(DefaultIndexDimmension::Step),
+        );
+        for i in 0..100 {
+            group.start_record(i);
+            group.add(TestReadings::Reading0, i as f64);
+        }
+    }
+    let stats = recorder.stats();
+    assert_eq!(stats.saved, 5);
+    assert_eq!(stats.dropped, 5);
+    assert_eq!(stats.sampled_out, 90);
+    assert_eq!(stats.overwritten_readings, 0);
+    assert!(stats.is_lossy());
+
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 3);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Reading0, 1.0);
+        group.add(TestReadings::Reading0, 2.0);
+        group.add(TestReadings::Reading1, 3.0);
+        group.start_record(1);
+        group.add(TestReadings::Reading0, 4.0);
+    }
+    let stats = recorder.stats();
+    assert_eq!(stats.saved, 2);
+    assert_eq!(stats.overwritten_readings, 1);
+    assert!(stats.is_lossy());
+}
+
+#[test]
+fn test_stats_count_overwrites_of_live_handles() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 3);
+    let mut group = recorder.add_group(RapidRecorderGroup::default());
+    group.start_record(0);
+    group.add(TestReadings::Reading0, 1.0);
+    assert!(!recorder.stats().is_lossy());
+    group.add(TestReadings::Reading0, 2.0);
+    // visible right away, not only once the handle is dropped
+    assert_eq!(recorder.stats().overwritten_readings, 1);
+    assert!(recorder.stats().is_lossy());
+    drop(group);
+    assert_eq!(recorder.stats().overwritten_readings, 1);
+}
+
+#[test]
+fn test_sparse_event_encoding() {
+    let recorder: RapidRecorder<_, TestReadings> =
+        RapidRecorder::new(100, 3).event_encoding(RREventEncoding::Sparse);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Reading2, 7.0);
+        group.start_record(1);
+        group.add(TestReadings::Reading0, 1.0);
+        group.add(TestReadings::Reading1, 2.0);
+    }
+
+    let first = recorder.raw_history().pop().unwrap();
+    assert_eq!(
+        first.readings.changed().collect::<Vec<_>>(),
+        vec![(2, RRValue::F64(7.0))]
+    );
+
+    let second = recorder.convenient_pop().unwrap();
+    assert_eq!(second.id, 1);
+    assert_eq!(second.values.len(), 2);
+    assert_eq!(second.values.get(&TestReadings::Reading0), Some(&1.0));
+    assert_eq!(second.values.get(&TestReadings::Reading1), Some(&2.0));
+}
+
+#[test]
+fn test_preallocated_event_pool() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(4, 3).preallocate_events();
+    assert_eq!(recorder.pooled_buffers_available(), Some(5));
+
+    for round in 0..3 {
+        {
+            let mut group = recorder.add_group(RapidRecorderGroup::default());
+            for i in 0..4 {
+                group.start_record(round * 4 + i);
+                if i % 2 == 0 {
+                    group.add(TestReadings::Reading0, i as f64);
+                } else {
+                    group.add(TestReadings::Reading1, i as f64);
+                }
+            }
+        }
+        assert_eq!(recorder.pooled_buffers_available(), Some(1));
+
+        let sorted = recorder.sorted_history();
+        let events = sorted.get(&DefaultIndexDimmension::Step).unwrap();
+        assert_eq!(events.len(), 4);
+        for event in events {
+            // recycled buffers must not leak readings from a previous event
+            assert_eq!(event.values.len(), 1);
+        }
+        assert_eq!(recorder.pooled_buffers_available(), Some(5));
+    }
+}
+
+#[test]
+fn test_history_view_does_not_consume() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 3);
+    let mut group = recorder.add_group(RapidRecorderGroup::default());
+    for i in 0..5 {
+        group.start_record(i);
+        group.add(TestReadings::Reading0, i as f64);
+    }
+    group._save_record();
+
+    let view = recorder.history_view();
+    assert_eq!(view.len(), 5);
+    assert_eq!(recorder.raw_history().len(), 5);
+
+    let sorted_view = recorder.sorted_history_view(RRDuplicateEventIdHandling::KeepOnlyFirst);
+    let ids: Vec<usize> = sorted_view[&DefaultIndexDimmension::Step]
+        .iter()
+        .map(|e| e.id)
+        .collect();
+    assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+
+    // recording continues after a view, and the final drain still sees everything
+    group.start_record(5);
+    group.add(TestReadings::Reading1, 1.0);
+    drop(group);
+    // record 4 was saved twice, once manually and once by start_record(5)
+    let sorted =
+        recorder.sorted_history_with_duplicate_handling(RRDuplicateEventIdHandling::KeepOnlyLast);
+    assert_eq!(sorted[&DefaultIndexDimmension::Step].len(), 6);
+    assert!(recorder.raw_history().is_empty());
+}
+
+#[test]
+fn test_history_view_while_save_is_blocked() {
+    let recorder: RapidRecorder<DefaultIndexDimmension, TestReadings> =
+        RapidRecorder::new(2, 3).overflow_policy(RROverflowPolicy::Block);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for i in 0..3 {
+                recorder._save_event(0, i);
+            }
+        });
+        while recorder.overflow_counts().blocked == 0 {
+            std::thread::yield_now();
+        }
+        // the consumer makes room whether or not the blocked save took the space the view needs to put events back
+        s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(recorder.convenient_pop().is_some());
+        });
+        // the blocked save must not keep the view waiting
+        let view = recorder.history_view();
+        assert_eq!(view.iter().map(|e| e.id).collect::<Vec<_>>(), vec![0, 1]);
+    });
+    assert_eq!(recorder.raw_history().len(), 2);
+    assert_eq!(recorder.stats().saved, 3);
+}
+
+#[test]
+fn test_history_view_keeps_history_under_concurrent_saves() {
+    for (policy, expected) in [
+        (RROverflowPolicy::DropNewest, 0..100),
+        (RROverflowPolicy::OverwriteOldest, 1900..2000),
+    ] {
+        let recorder: RapidRecorder<DefaultIndexDimmension, TestReadings> =
+            RapidRecorder::new(100, 3).overflow_policy(policy);
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    recorder.history_view();
+                }
+            });
+            for i in 0..2000 {
+                recorder._save_event(0, i);
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        // views running alongside the saves must leave exactly what the overflow policy alone would have kept
+        let ids: Vec<usize> = std::iter::from_fn(|| recorder.convenient_pop())
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(ids, expected.collect::<Vec<_>>());
+        let counts = recorder.overflow_counts();
+        assert_eq!(counts.dropped_newest + counts.overwritten_oldest, 1900);
+    }
+}
+
+#[test]
+fn test_drain_thread_forwards_every_event() {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    {
+        // a tiny buffer that would overflow immediately without the drain thread
+        let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(4, 3)
+            .overflow_policy(RROverflowPolicy::Block)
+            .drain_to(
+                move |event: rapid_recorder::event::RawRREvent| {
+                    sink.lock().unwrap().push(event.record_id)
+                },
+                Duration::from_millis(1),
+            );
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        for i in 0..1000 {
+            group.start_record(i);
+            group.add(TestReadings::Reading0, i as f64);
+        }
+        drop(group);
+        assert_eq!(recorder.stats().dropped, 0);
+    }
+    // dropping the recorder flushes whatever was left in the buffer
+    assert_eq!(*received.lock().unwrap(), (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_drain_to_channel_and_writer() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    {
+        let recorder: RapidRecorder<_, TestReadings> =
+            RapidRecorder::new(100, 3).drain_to(sender, Duration::from_millis(1));
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(7);
+        group.add(TestReadings::Reading1, 2.5);
+    }
+    let event = receiver
+        .recv()
+        .unwrap()
+        .to_rr_event::<TestReadings, DefaultIndexDimmension>();
+    assert_eq!(event.id, 7);
+    assert_eq!(event.values.get(&TestReadings::Reading1), Some(&2.5));
+
+    #[derive(Clone, Default)]
+    struct SharedBytes(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for SharedBytes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let bytes = SharedBytes::default();
+    {
+        let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 3)
+            .drain_to(RRWriterSink::new(bytes.clone()), Duration::from_millis(1));
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(3);
+        group.add(TestReadings::Reading2, 1.5);
+    }
+    let text = String::from_utf8(bytes.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(text, "1 3 2=1.5\n");
+}
+
+#[test]
+fn test_drain_thread_recycles_preallocated_events() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(4, 3)
+        .overflow_policy(RROverflowPolicy::Block)
+        .preallocate_events()
+        .drain_to(RRWriterSink::new(std::io::sink()), Duration::from_millis(1));
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        for i in 0..1000 {
+            group.start_record(i);
+            group.add(TestReadings::Reading0, i as f64);
+        }
+    }
+    // once the sink is done with the last event, every buffer is back in the pool
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while recorder.pooled_buffers_available() != Some(5) {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "buffers never came back"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(recorder.stats().saved, 1000);
+}
+
+#[test]
+fn test_typed_readings() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 3);
+    let big_id: u64 = (1 << 53) + 1;
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Reading0, big_id);
+        group.add(TestReadings::Reading1, -42i64);
+        group.add(TestReadings::Reading2, true);
+    }
+    let event = recorder.convenient_pop().unwrap();
+    assert_eq!(event.get::<u64>(TestReadings::Reading0), Some(big_id));
+    assert_eq!(event.get::<i64>(TestReadings::Reading1), Some(-42));
+    assert_eq!(event.get::<bool>(TestReadings::Reading2), Some(true));
+    // asking for the wrong type gives nothing rather than a silently converted value
+    assert_eq!(event.get::<f64>(TestReadings::Reading0), None);
+    // the f64 view is still there for float-only code
+    assert_eq!(event.values.get(&TestReadings::Reading2), Some(&1.0));
+}
+
+#[test]
+fn test_simulated_clock() {
+    let clock = RRSimulatedClock::new();
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 3).clock(clock.clone());
+    {
+        let mut group = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Time),
+        );
+        for _ in 0..3 {
+            clock.advance(Duration::from_millis(5));
+            group.start_record_now();
+            group.add(TestReadings::Reading1, 1.0);
+            clock.advance(Duration::from_millis(2));
+        }
+        // the clock never runs backwards
+        clock.set(Duration::ZERO);
+    }
+
+    let sorted = recorder.sorted_history();
+    let events = &sorted[&DefaultIndexDimmension::Time];
+    let ids: Vec<usize> = events.iter().map(|e| e.id).collect();
+    assert_eq!(ids, vec![5_000_000, 12_000_000, 19_000_000]);
+    for event in events {
+        assert_eq!(event.timestamps.started.as_nanos() as usize, event.id);
+        assert!(event.timestamps.started_wall.is_none());
+    }
+    // a record is saved when the next one starts, the last one when the handle drops
+    assert_eq!(events[0].timestamps.saved, events[1].timestamps.started);
+    assert_eq!(events[2].timestamps.duration(), Duration::from_millis(2));
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_tsc_clock_runs_forward() {
+    let clock = RRTscClock::calibrated_over(Duration::from_millis(5));
+    assert!(clock.nanos_per_tick() > 0.0);
+    let before = clock.now();
+    std::thread::sleep(Duration::from_millis(10));
+    let elapsed = clock.now() - before;
+    assert!(elapsed >= Duration::from_millis(5), "{:?}", elapsed);
+
+    // never behind a time already handed out, from any thread
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let mut last = clock.now();
+                for _ in 0..10_000 {
+                    let now = clock.now();
+                    assert!(now >= last);
+                    last = now;
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn test_groups_keep_their_own_readings() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+    {
+        let mut fast = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Step),
+        );
+        let mut slow = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryTen)
+                .index_type(DefaultIndexDimmension::Run),
+        );
+        for i in 0..20 {
+            slow.start_record(i);
+            fast.start_record(i);
+            fast.add(TestReadings::Reading0, i as f64);
+            slow.add(TestReadings::Reading1, i as f64);
+            // the same reading name in both groups stays separate too
+            fast.add(TestReadings::Reading2, 1.0);
+            slow.add(TestReadings::Reading2, 2.0);
+        }
+    }
+
+    let sorted = recorder.sorted_history();
+    let fast_events = &sorted[&DefaultIndexDimmension::Step];
+    assert_eq!(fast_events.len(), 20);
+    for event in fast_events {
+        assert_eq!(
+            event.values.get(&TestReadings::Reading0),
+            Some(&(event.id as f64))
+        );
+        assert_eq!(event.values.get(&TestReadings::Reading1), None);
+        assert_eq!(event.values.get(&TestReadings::Reading2), Some(&1.0));
+    }
+    let slow_events = &sorted[&DefaultIndexDimmension::Run];
+    assert_eq!(slow_events.len(), 2);
+    for event in slow_events {
+        // the fast group saving in between did not take the slow group's reading
+        assert_eq!(
+            event.values.get(&TestReadings::Reading1),
+            Some(&(event.id as f64))
+        );
+        assert_eq!(event.values.get(&TestReadings::Reading0), None);
+        assert_eq!(event.values.get(&TestReadings::Reading2), Some(&2.0));
+    }
+    assert_eq!(recorder.stats().overwritten_readings, 0);
+}
+
+#[test]
+fn test_history_by_group() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+    {
+        let mut step = recorder.add_group(RapidRecorderGroup::default().name("step"));
+        let mut substep = recorder.add_group(RapidRecorderGroup::default().name("substep"));
+        let mut unnamed = recorder.add_group(RapidRecorderGroup::default());
+        for i in 0..3 {
+            step.start_record(i);
+            step.add(TestReadings::Reading0, i as f64);
+            for j in 0..2 {
+                substep.start_record(i * 2 + j);
+                substep.add(TestReadings::Reading1, j as f64);
+            }
+            unnamed.start_record(i);
+            unnamed.add(TestReadings::Reading2, 1.0);
+        }
+    }
+
+    let view = recorder.history_view();
+    assert_eq!(
+        view.iter()
+            .filter(|e| e.group.as_deref() == Some("substep"))
+            .count(),
+        6
+    );
+
+    // the groups reuse record IDs on the same index type, but duplicate handling stays within a group
+    let mut by_group = recorder.sorted_history_by_group(RRDuplicateEventIdHandling::KeepOnlyFirst);
+    assert_eq!(by_group.len(), 3);
+    let step = by_group.remove(&Some("step".into())).unwrap();
+    let step = &step[&DefaultIndexDimmension::Step];
+    assert_eq!(step.iter().map(|e| e.id).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert!(
+        step.iter()
+            .all(|e| e.values.contains_key(&TestReadings::Reading0))
+    );
+    let substep = by_group.remove(&Some("substep".into())).unwrap();
+    assert_eq!(substep[&DefaultIndexDimmension::Step].len(), 6);
+    let unnamed = by_group.remove(&None).unwrap();
+    assert_eq!(unnamed[&DefaultIndexDimmension::Step].len(), 3);
+    assert!(recorder.raw_history().is_empty());
+}
+
+#[test]
+fn test_nested_index_paths() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+    {
+        let mut run = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Run),
+        );
+        let mut step = run.add_child_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Step),
+        );
+        let mut cell = step.add_child_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::I),
+        );
+        for r in 0..2 {
+            run.start_record(r);
+            for s in 0..3 {
+                step.start_record(s);
+                step.add(TestReadings::Reading0, s as f64);
+                for i in 0..4 {
+                    cell.start_record(i);
+                    cell.add(TestReadings::Reading1, (r * 100 + s * 10 + i) as f64);
+                }
+            }
+        }
+    }
+
+    let view = recorder.history_view();
+    let last_cell = view
+        .iter()
+        .find(|e| {
+            e.iteration_index == DefaultIndexDimmension::I
+                && e.values[&TestReadings::Reading1] == 123.0
+        })
+        .unwrap();
+    // saved after the step moved on, but the path was captured when the record started
+    assert_eq!(
+        last_cell.path,
+        vec![
+            (DefaultIndexDimmension::Run, 1),
+            (DefaultIndexDimmension::Step, 2),
+            (DefaultIndexDimmension::I, 3)
+        ]
+    );
+    assert_eq!(last_cell.index_at(DefaultIndexDimmension::Step), Some(2));
+    assert_eq!(last_cell.index_at(DefaultIndexDimmension::J), None);
+
+    let by_step = recorder.sorted_history_view_by_level(
+        DefaultIndexDimmension::Step,
+        RRDuplicateEventIdHandling::KeepOnlyFirst,
+    );
+    // run events aren't nested under a step
+    assert_eq!(by_step[&None][&DefaultIndexDimmension::Run].len(), 2);
+    let step_one = &by_step[&Some(1)];
+    // step 1 of both runs, KeepOnlyFirst only dedups within the slice, so each cell ID survives once
+    assert_eq!(step_one[&DefaultIndexDimmension::Step].len(), 1);
+    assert_eq!(step_one[&DefaultIndexDimmension::I].len(), 4);
+
+    let by_run = recorder.sorted_history_by_level(
+        DefaultIndexDimmension::Run,
+        RRDuplicateEventIdHandling::KeepBoth,
+    );
+    let run_one = &by_run[&Some(1)];
+    assert_eq!(run_one[&DefaultIndexDimmension::Run].len(), 1);
+    assert_eq!(run_one[&DefaultIndexDimmension::Step].len(), 3);
+    assert_eq!(run_one[&DefaultIndexDimmension::I].len(), 12);
+    assert!(
+        run_one[&DefaultIndexDimmension::I]
+            .iter()
+            .all(|e| e.values[&TestReadings::Reading1] >= 100.0)
+    );
+}
+
+#[test]
+fn test_sample_interval() {
+    let clock = RRSimulatedClock::new();
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3).clock(clock.clone());
+    {
+        let mut group = recorder.add_group(
+            RapidRecorderGroup::<DefaultSamplingFrequency>::new()
+                .index_type(DefaultIndexDimmension::Step)
+                .sample_interval(Duration::from_millis(5)),
+        );
+        for step in 0..30 {
+            group.start_record(step);
+            group.add(TestReadings::Reading0, step as f64);
+            // steps speed up after the first ten, the sampling keeps the same spacing in time
+            clock.advance(Duration::from_millis(if step < 10 { 1 } else { 2 }));
+        }
+    }
+    let sorted = recorder.sorted_history();
+    let ids: Vec<usize> = sorted[&DefaultIndexDimmension::Step]
+        .iter()
+        .map(|e| e.id)
+        .collect();
+    assert_eq!(ids, vec![0, 5, 10, 13, 16, 19, 22, 25, 28]);
+    assert_eq!(recorder.stats().sampled_out, 30 - ids.len());
+}
+
+#[test]
+fn test_sample_schedules() {
+    let saved_ids = |group: RapidRecorderGroup| {
+        let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+        {
+            let mut handle = recorder.add_group(group);
+            for id in 0..300 {
+                handle.start_record(id);
+                handle.add(TestReadings::Reading0, id as f64);
+            }
+        }
+        let ids: Vec<usize> = recorder
+            .sorted_history()
+            .remove(&DefaultIndexDimmension::Step)
+            .unwrap_or_default()
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(recorder.stats().sampled_out, 300 - ids.len());
+        ids
+    };
+
+    assert_eq!(
+        saved_ids(
+            RapidRecorderGroup::default().sample_schedule(RRSampleSchedule::LogSpaced { base: 2 })
+        ),
+        vec![0, 1, 2, 4, 8, 16, 32, 64, 128, 256]
+    );
+    let decades =
+        saved_ids(RapidRecorderGroup::default().sample_schedule(RRSampleSchedule::Decades));
+    assert_eq!(&decades[..12], &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 20]);
+    assert_eq!(&decades[decades.len() - 3..], &[90, 100, 200]);
+    assert_eq!(
+        saved_ids(
+            RapidRecorderGroup::default().sample_schedule(RRSampleSchedule::Burst {
+                length: 2,
+                every: 100
+            })
+        ),
+        vec![0, 1, 100, 101, 200, 201]
+    );
+    assert!(
+        saved_ids(
+            RapidRecorderGroup::default().sample_schedule(RRSampleSchedule::Burst {
+                length: 2,
+                every: 0
+            })
+        )
+        .is_empty()
+    );
+    assert_eq!(
+        saved_ids(
+            RapidRecorderGroup::default()
+                .sample_rate(DefaultSamplingFrequency::EveryHundred)
+                .sample_offset(42)
+        ),
+        vec![42, 142, 242]
+    );
+    // offsets shift schedules too, and predicates combine with the stride
+    assert_eq!(
+        saved_ids(
+            RapidRecorderGroup::default()
+                .sample_rate(DefaultSamplingFrequency::EveryTen)
+                .sample_offset(5)
+                .sample_schedule(RRSampleSchedule::predicate(|id| id % 70 == 0))
+        ),
+        vec![5, 75, 145, 215, 285]
+    );
+}
+
+#[test]
+fn test_deadband() {
+    let saved_ids = |deadband: RRDeadband| {
+        let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+        {
+            let mut group = recorder.add_group(RapidRecorderGroup::default().deadband(deadband));
+            for id in 0..100 {
+                group.start_record(id);
+                // a plateau with a little noise, a step at 40 and a slow drift from 70
+                let noise = if id % 2 == 0 { 0.01 } else { -0.01 };
+                let level = match id {
+                    0..40 => 10.0,
+                    40..70 => 20.0,
+                    _ => 20.0 + (id - 70) as f64 * 0.3,
+                };
+                group.add(TestReadings::Reading0, level + noise);
+                if id == 90 {
+                    // a reading seen for the first time always counts as a change
+                    group.add(TestReadings::Reading1, true);
+                }
+            }
+        }
+        let ids: Vec<usize> = recorder.sorted_history()[&DefaultIndexDimmension::Step]
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(recorder.stats().sampled_out, 100 - ids.len());
+        ids
+    };
+
+    assert_eq!(
+        saved_ids(RRDeadband::Absolute(1.0)),
+        vec![0, 40, 74, 78, 82, 86, 90, 94, 98]
+    );
+    // 10% of 20 is 2, so the drift saves less often than with an absolute threshold of 1
+    assert_eq!(
+        saved_ids(RRDeadband::Relative(0.1)),
+        vec![0, 40, 77, 85, 90, 99]
+    );
+}
+
+#[test]
+fn test_trigger() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+    {
+        let trigger = RRTrigger::new(TestReadings::Reading0, |v| {
+            let residual = v.as_f64();
+            residual.is_nan() || residual > 100.0
+        })
+        .before(3)
+        .after(2);
+        let mut group = recorder.add_group(RapidRecorderGroup::default().trigger(trigger));
+        for id in 0..100 {
+            group.start_record(id);
+            let residual = match id {
+                20 => f64::NAN,
+                60 | 62 => 500.0,
+                _ => 1.0,
+            };
+            group.add(TestReadings::Reading0, residual);
+        }
+    }
+    let ids: Vec<usize> = recorder.sorted_history()[&DefaultIndexDimmension::Step]
+        .iter()
+        .map(|e| e.id)
+        .collect();
+    // a second trigger inside the records after the first extends them
+    assert_eq!(
+        ids,
+        vec![17, 18, 19, 20, 21, 22, 57, 58, 59, 60, 61, 62, 63, 64]
+    );
+    assert_eq!(recorder.stats().sampled_out, 100 - ids.len());
+}
+
+#[test]
+#[should_panic(expected = "RRTrigger watches a")]
+fn test_trigger_on_other_readings_panics() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 3);
+    let trigger = RRTrigger::new(DefaultSamplingFrequency::EveryOne, |_| true);
+    recorder.add_group(RapidRecorderGroup::default().trigger(trigger));
+}
+
+#[test]
+fn test_record_guard() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3);
+    let mut group = recorder.add_group(RapidRecorderGroup::default());
+    for id in 0..5 {
+        let record = group.record(id);
+        record.add(TestReadings::Reading0, id as u64);
+        if id == 3 {
+            continue;
+        }
+        record.add(TestReadings::Reading1, true);
+    }
+    // every record is saved as soon as its guard goes away, not when the next one starts
+    assert_eq!(recorder.stats().saved, 5);
+    // readings between records don't end up in the next one
+    group.add(TestReadings::Reading2, 1.0);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let record = group.record(5);
+        record.add(TestReadings::Reading0, 5u64);
+        panic!("simulation blew up");
+    }));
+    assert!(result.is_err());
+    assert_eq!(recorder.stats().saved, 6);
+    drop(group);
+    assert_eq!(recorder.stats().saved, 6);
+
+    let history = recorder.sorted_history();
+    let events = &history[&DefaultIndexDimmension::Step];
+    assert_eq!(
+        events.iter().map(|e| e.id).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4, 5]
+    );
+    for event in events {
+        assert_eq!(
+            event.get(TestReadings::Reading0),
+            Some(RRValue::U64(event.id as u64))
+        );
+        assert_eq!(
+            event.values.contains_key(&TestReadings::Reading1),
+            event.id < 5 && event.id != 3
+        );
+        assert!(!event.values.contains_key(&TestReadings::Reading2));
+    }
+}
+
+#[test]
+fn test_pause_resume() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(1000, 3).start_paused();
+    assert!(!recorder.is_recording());
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        for id in 0..30 {
+            // another thread turns recording on and off while the loop runs
+            match id {
+                10 => std::thread::scope(|s| {
+                    s.spawn(|| recorder.resume());
+                }),
+                20 => std::thread::scope(|s| {
+                    s.spawn(|| recorder.pause());
+                }),
+                _ => {}
+            }
+            group.start_record(id);
+            group.add(TestReadings::Reading0, id as u64);
+            if id == 19 {
+                recorder.pause();
+                // too late for this record's reading, but the record itself is still saved
+                group.add(TestReadings::Reading1, true);
+                recorder.resume();
+            }
+        }
+        let shared = recorder.add_shared_group(RapidRecorderGroup::default());
+        assert!(!shared.start_record(100).is_sampled());
+    }
+    let history = recorder.sorted_history();
+    let events = &history[&DefaultIndexDimmension::Step];
+    assert_eq!(
+        events.iter().map(|e| e.id).collect::<Vec<_>>(),
+        (10..20).collect::<Vec<_>>()
+    );
+    assert!(
+        events
+            .iter()
+            .all(|e| !e.values.contains_key(&TestReadings::Reading1))
+    );
+    let stats = recorder.stats();
+    assert_eq!(stats.saved, 10);
+    // records skipped while paused are not sampled out
+    assert_eq!(stats.sampled_out, 0);
+}
@@ -3,7 +3,7 @@ use rayon::prelude::*;
 use strum_macros::EnumIter;
 
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 
 enum TestReadings {
     Reading0,
@@ -95,3 +95,240 @@ fn test_parallel_multi_level() {
     // Verify we recorded something
     assert_eq!(recorder.raw_history().len(), 1);
 }
+
+#[test]
+fn test_parallel_accumulating_modes() {
+    #[repr(u32)]
+    #[derive(EnumIter, Debug)]
+    enum Stats {
+        Total,
+        Smallest,
+        Largest,
+        Contributions,
+        Average,
+    }
+    impl_rapid_recorder_named_usize!(Stats);
+
+    let recorder: RapidRecorder<_, Stats> = RapidRecorder::new(100, 5)
+        .reading_mode(Stats::Total, RRReadingMode::Sum)
+        .reading_mode(Stats::Smallest, RRReadingMode::Min)
+        .reading_mode(Stats::Largest, RRReadingMode::Max)
+        .reading_mode(Stats::Contributions, RRReadingMode::Count)
+        .reading_mode(Stats::Average, RRReadingMode::Mean);
+    {
+        let mut group = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Step),
+        );
+        for step in 0..3i64 {
+            group.start_record(step as usize);
+            (1..=1000i64).into_par_iter().for_each(|i| {
+                let value = i * (step + 1);
+                group.add(Stats::Total, value);
+                group.add(Stats::Smallest, value);
+                group.add(Stats::Largest, value);
+                group.add(Stats::Contributions, value);
+                group.add(Stats::Average, value as f64);
+            });
+        }
+    }
+
+    let sorted = recorder.sorted_history();
+    let events = &sorted[&DefaultIndexDimmension::Step];
+    assert_eq!(events.len(), 3);
+    for (step, event) in events.iter().enumerate() {
+        // accumulators reset between records, so every step only sees its own values
+        let scale = step as i64 + 1;
+        assert_eq!(event.get::<i64>(Stats::Total), Some(500_500 * scale));
+        assert_eq!(event.get::<i64>(Stats::Smallest), Some(scale));
+        assert_eq!(event.get::<i64>(Stats::Largest), Some(1000 * scale));
+        assert_eq!(event.get::<u64>(Stats::Contributions), Some(1000));
+        assert_eq!(event.get::<f64>(Stats::Average), Some(500.5 * scale as f64));
+    }
+    assert_eq!(recorder.stats().overwritten_readings, 0);
+}
+
+#[test]
+fn test_parallel_first_integer_values() {
+    // the first integer added to a fresh slot switches it away from f64, which every worker races to do
+    for _ in 0..50 {
+        let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 3)
+            .reading_mode(TestReadings::Reading0, RRReadingMode::Sum)
+            .reading_mode(TestReadings::Reading1, RRReadingMode::Min)
+            .reading_mode(TestReadings::Reading2, RRReadingMode::Max);
+        {
+            let mut group = recorder.add_group(RapidRecorderGroup::default());
+            group.start_record(0);
+            (1..=1000u64).into_par_iter().for_each(|i| {
+                group.add(TestReadings::Reading0, i);
+                group.add(TestReadings::Reading1, i);
+                group.add(TestReadings::Reading2, i);
+            });
+        }
+        let event = recorder.convenient_pop().unwrap();
+        assert_eq!(event.get::<u64>(TestReadings::Reading0), Some(500_500));
+        assert_eq!(event.get::<u64>(TestReadings::Reading1), Some(1));
+        assert_eq!(event.get::<u64>(TestReadings::Reading2), Some(1000));
+    }
+}
+
+#[test]
+fn test_parallel_histogram() {
+    #[repr(u32)]
+    #[derive(EnumIter, Debug)]
+    enum Latency {
+        Micros,
+        Scaled,
+    }
+    impl_rapid_recorder_named_usize!(Latency);
+
+    let recorder: RapidRecorder<_, Latency> = RapidRecorder::new(100, 2)
+        .reading_mode(
+            Latency::Micros,
+            RRReadingMode::Histogram(RRHistogramBuckets::Linear {
+                min: 0.0,
+                max: 100.0,
+                count: 10,
+            }),
+        )
+        .reading_mode(
+            Latency::Scaled,
+            RRReadingMode::Histogram(RRHistogramBuckets::Log {
+                min: 1.0,
+                max: 1000.0,
+                count: 3,
+            }),
+        );
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        (0..1000u64).into_par_iter().for_each(|i| {
+            // 0..=99 evenly, plus one overflow and one underflow per hundred
+            group.add(Latency::Micros, (i % 100) as f64);
+            group.add(Latency::Scaled, i as f64);
+        });
+        group.add(Latency::Micros, 250.0);
+        group.add(Latency::Micros, -1.0);
+        group.start_record(1);
+        // nothing added, so the next record has no histograms
+    }
+
+    let sorted = recorder.sorted_history();
+    let events = &sorted[&DefaultIndexDimmension::Step];
+    assert_eq!(events.len(), 2);
+    let micros = events[0].histogram(Latency::Micros).unwrap();
+    assert_eq!(micros.total(), 1002);
+    assert_eq!(micros.underflow(), 1);
+    assert_eq!(micros.overflow(), 1);
+    assert!(micros.counts[1..11].iter().all(|c| *c == 100));
+    assert_eq!(micros.iter().nth(3).unwrap().0, (20.0, 30.0));
+
+    // 0 underflows, then [1, 10), [10, 100), [100, 1000)
+    let scaled = events[0].histogram(Latency::Scaled).unwrap();
+    assert_eq!(scaled.counts, vec![1, 9, 90, 900, 0]);
+    // histogram readings aren't part of the regular readings
+    assert!(events[0].typed_values.is_empty());
+
+    assert!(events[1].histograms.is_empty());
+}
+
+#[test]
+fn test_parallel_mean_variance() {
+    #[repr(u32)]
+    #[derive(EnumIter, Debug)]
+    enum Solver {
+        Residual,
+    }
+    impl_rapid_recorder_named_usize!(Solver);
+
+    let recorder: RapidRecorder<_, Solver> =
+        RapidRecorder::new(100, 1).reading_mode(Solver::Residual, RRReadingMode::MeanVariance);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        for step in 0..2 {
+            group.start_record(step);
+            // a large offset makes naive sum of squares lose every digit of the variance
+            (1..=1000u64).into_par_iter().for_each(|i| {
+                group.add(Solver::Residual, 1e9 + i as f64 * (step + 1) as f64);
+            });
+        }
+    }
+
+    let sorted = recorder.sorted_history();
+    let events = &sorted[&DefaultIndexDimmension::Step];
+    assert_eq!(events.len(), 2);
+    let first = events[0].moments(Solver::Residual).unwrap();
+    assert_eq!(first.count, 1000);
+    assert!((first.mean - (1e9 + 500.5)).abs() < 1e-6);
+    // population variance of 1..=n is (n^2 - 1) / 12
+    let expected = (1000.0f64 * 1000.0 - 1.0) / 12.0;
+    assert!((first.variance() - expected).abs() / expected < 1e-9);
+    assert!(events[0].typed_values.is_empty());
+
+    // accumulators reset between records
+    let second = events[1].moments(Solver::Residual).unwrap();
+    assert_eq!(second.count, 1000);
+    assert!((second.variance() - 4.0 * expected).abs() / expected < 1e-9);
+
+    let both = first.merge(second);
+    assert_eq!(both.count, 2000);
+    assert!((both.mean - (1e9 + 750.75)).abs() < 1e-6);
+}
+
+#[test]
+fn test_shared_group_concurrent_records() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10_000, 3);
+    {
+        let group = recorder.add_shared_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryTen)
+                .index_type(DefaultIndexDimmension::Step),
+        );
+        assert_send_sync(&group);
+        // every thread starts and saves its own records, with no single thread managing the index
+        (0..1000usize).into_par_iter().for_each(|id| {
+            let record = group.start_record(id);
+            assert_eq!(record.is_sampled(), id % 10 == 0);
+            record.add(TestReadings::Reading0, id as u64);
+            record.add(TestReadings::Reading1, (id * 2) as u64);
+        });
+    }
+    let history = recorder.sorted_history();
+    let events = &history[&DefaultIndexDimmension::Step];
+    assert_eq!(events.len(), 100);
+    for (i, event) in events.iter().enumerate() {
+        assert_eq!(event.id, i * 10);
+        // readings never leak between records started on different threads
+        assert_eq!(
+            event.get(TestReadings::Reading0),
+            Some(RRValue::U64(event.id as u64))
+        );
+        assert_eq!(
+            event.get(TestReadings::Reading1),
+            Some(RRValue::U64(event.id as u64 * 2))
+        );
+    }
+    let stats = recorder.stats();
+    assert_eq!(stats.sampled_out, 900);
+    assert!(!stats.is_lossy());
+}
+
+#[test]
+fn test_shared_group_reports_overwrites_while_alive() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10_000, 3);
+    let group = recorder.add_shared_group(RapidRecorderGroup::default());
+    (0..100usize).into_par_iter().for_each(|id| {
+        let record = group.start_record(id);
+        record.add(TestReadings::Reading0, 1.0);
+        if id % 10 == 0 {
+            record.add(TestReadings::Reading0, 2.0);
+        }
+    });
+    // a long-lived handle, e.g. in a server, still shows up in the stats while it is in use
+    assert_eq!(recorder.stats().overwritten_readings, 10);
+    assert!(recorder.stats().is_lossy());
+    drop(group);
+    assert_eq!(recorder.stats().overwritten_readings, 10);
+}
@@ -0,0 +1,582 @@
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rapid_recorder::prelude::*;
+use rapid_recorder::recording::{RECORDING_FORMAT_VERSION, RRVariantInfo};
+use strum_macros::EnumIter;
+
+#[repr(u32)]
+#[derive(EnumIter, Debug)]
+enum TestReadings {
+    Temperature,
+    Pressure,
+}
+impl_rapid_recorder_named_usize!(TestReadings);
+
+#[repr(u32)]
+#[derive(EnumIter, Debug)]
+enum OtherReadings {
+    Temperature,
+    Humidity,
+}
+impl_rapid_recorder_named_usize!(OtherReadings);
+
+fn record_steps(recorder: &RapidRecorder<DefaultIndexDimmension, TestReadings>) {
+    let mut group = recorder.add_group(RapidRecorderGroup::default());
+    for step in 0..3 {
+        group.start_record(step);
+        group.add(TestReadings::Temperature, step as f64 * 10.0);
+        if step == 1 {
+            group.add(TestReadings::Pressure, 101.3);
+        }
+    }
+}
+
+#[test]
+fn test_recording_roundtrip() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 2);
+    record_steps(&recorder);
+    let mut bytes = Vec::new();
+    assert_eq!(recorder.save_recording(&mut bytes).unwrap(), 3);
+    assert!(recorder.raw_history().is_empty());
+
+    let reader = RecordingReader::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(reader.version(), RECORDING_FORMAT_VERSION);
+    assert_eq!(
+        reader.reading_variants(),
+        &[
+            RRVariantInfo {
+                discriminant: 0,
+                name: "Temperature".to_string()
+            },
+            RRVariantInfo {
+                discriminant: 1,
+                name: "Pressure".to_string()
+            },
+        ]
+    );
+    let events: Vec<_> = reader
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[1].id, 1);
+    assert_eq!(events[1].iteration_index, DefaultIndexDimmension::Step);
+    assert_eq!(
+        events[1].values.get(&TestReadings::Temperature),
+        Some(&10.0)
+    );
+    assert_eq!(events[1].values.get(&TestReadings::Pressure), Some(&101.3));
+    assert_eq!(events[2].values.len(), 1);
+
+    // without the enum types, readings are keyed by name
+    let named: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .named_events()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(named[1].iteration_index, "Step");
+    assert_eq!(named[1].values.get("Pressure"), Some(&RRValue::F64(101.3)));
+
+    // a different enum is rejected rather than silently misread
+    assert!(
+        RecordingReader::new(Cursor::new(&bytes))
+            .unwrap()
+            .events::<OtherReadings, DefaultIndexDimmension>()
+            .is_err()
+    );
+    assert!(RecordingReader::new(Cursor::new(b"not a recording")).is_err());
+}
+
+#[derive(Clone, Default)]
+struct SharedBytes(Arc<Mutex<Vec<u8>>>);
+impl Write for SharedBytes {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_recording_writer_as_drain_sink() {
+    let bytes = SharedBytes::default();
+    {
+        let writer =
+            RecordingWriter::new::<DefaultIndexDimmension, TestReadings>(bytes.clone()).unwrap();
+        let recorder: RapidRecorder<_, TestReadings> =
+            RapidRecorder::new(100, 2).drain_to(writer, Duration::from_millis(1));
+        record_steps(&recorder);
+    }
+    let bytes = bytes.0.lock().unwrap().clone();
+    let ids: Vec<usize> = RecordingReader::new(Cursor::new(bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .map(|e| e.unwrap().id)
+        .collect();
+    assert_eq!(ids, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_export_csv() {
+    let export = |unchanged_readings| {
+        let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 2);
+        record_steps(&recorder);
+        let mut bytes = Vec::new();
+        let rows = recorder
+            .export_csv(
+                &mut bytes,
+                RRCsvOptions {
+                    unchanged_readings,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(rows, 3);
+        String::from_utf8(bytes).unwrap()
+    };
+
+    assert_eq!(
+        export(RRUnchangedReadings::Blank),
+        "index_type,record_id,Temperature,Pressure\n\
+         Step,0,0,\n\
+         Step,1,10,101.3\n\
+         Step,2,20,\n"
+    );
+    assert_eq!(
+        export(RRUnchangedReadings::NaN),
+        "index_type,record_id,Temperature,Pressure\n\
+         Step,0,0,NaN\n\
+         Step,1,10,101.3\n\
+         Step,2,20,NaN\n"
+    );
+    assert_eq!(
+        export(RRUnchangedReadings::ForwardFill),
+        "index_type,record_id,Temperature,Pressure\n\
+         Step,0,0,\n\
+         Step,1,10,101.3\n\
+         Step,2,20,101.3\n"
+    );
+}
+
+#[test]
+fn test_typed_values_survive_recording_and_csv() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 2);
+    let big_id: u64 = u64::MAX - 1;
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Temperature, 21.5);
+        group.add(TestReadings::Pressure, big_id);
+    }
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+    let event = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.get::<u64>(TestReadings::Pressure), Some(big_id));
+
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Temperature, false);
+        group.add(TestReadings::Pressure, big_id);
+    }
+    let mut csv = Vec::new();
+    recorder
+        .export_csv(&mut csv, RRCsvOptions::default())
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        format!(
+            "index_type,record_id,Temperature,Pressure\nStep,0,false,{}\n",
+            big_id
+        )
+    );
+}
+
+#[test]
+fn test_histograms_roundtrip() {
+    let buckets = RRHistogramBuckets::Log {
+        min: 0.1,
+        max: 1000.0,
+        count: 4,
+    };
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2)
+        .reading_mode(TestReadings::Pressure, RRReadingMode::Histogram(buckets));
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        for value in [0.5, 5.0, 50.0, 500.0, 5000.0] {
+            group.add(TestReadings::Pressure, value);
+        }
+        group.add(TestReadings::Temperature, 20.0);
+    }
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+
+    let events: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let histogram = events[0].histogram(TestReadings::Pressure).unwrap();
+    assert_eq!(histogram.buckets, buckets);
+    assert_eq!(histogram.counts, vec![0, 1, 1, 1, 1, 1]);
+    assert_eq!(events[0].get::<f64>(TestReadings::Temperature), Some(20.0));
+
+    let named: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .named_events()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(named[0].histograms["Pressure"].total(), 5);
+}
+
+#[test]
+fn test_moments_roundtrip() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2)
+        .reading_mode(TestReadings::Temperature, RRReadingMode::MeanVariance);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            group.add(TestReadings::Temperature, value);
+        }
+    }
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+
+    let events: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let moments = events[0].moments(TestReadings::Temperature).unwrap();
+    assert_eq!(moments.count, 8);
+    assert_eq!(moments.mean, 5.0);
+    assert_eq!(moments.std_dev(), 2.0);
+}
+
+#[test]
+fn test_timestamps() {
+    let before = std::time::SystemTime::now();
+    let recorder: RapidRecorder<_, TestReadings> =
+        RapidRecorder::new(10, 2).wall_clock_timestamps();
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Temperature, 1.0);
+        std::thread::sleep(Duration::from_millis(20));
+        group.start_record(1);
+        group.add(TestReadings::Temperature, 2.0);
+    }
+    let after = std::time::SystemTime::now();
+
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+    let events: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let (first, second) = (events[0].timestamps, events[1].timestamps);
+    assert!(first.duration() >= Duration::from_millis(20));
+    assert!(first.started <= first.saved);
+    assert!(first.saved <= second.started);
+    assert!(second.started <= second.saved);
+    for wall in [first.started_wall, second.saved_wall] {
+        // the recording keeps nanoseconds, allow for rounding of the epoch
+        let wall = wall.unwrap();
+        assert!(wall + Duration::from_millis(1) >= before && wall <= after);
+    }
+
+    // without wall clock timestamps the unix columns stay blank
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2);
+    record_steps(&recorder);
+    let mut csv = Vec::new();
+    recorder
+        .export_csv(
+            &mut csv,
+            RRCsvOptions {
+                timestamps: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "index_type,record_id,started_s,saved_s,started_unix_s,saved_unix_s,Temperature,Pressure"
+    );
+    let cells: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(cells.len(), 8);
+    assert!(cells[2].parse::<f64>().unwrap() <= cells[3].parse::<f64>().unwrap());
+    assert_eq!(&cells[4..6], &["", ""]);
+}
+
+#[test]
+fn test_groups_in_csv_and_recording() {
+    let record = |recorder: &RapidRecorder<DefaultIndexDimmension, TestReadings>| {
+        let mut outer = recorder.add_group(RapidRecorderGroup::default().name("outer"));
+        let mut inner = recorder.add_group(RapidRecorderGroup::default());
+        outer.start_record(0);
+        outer.add(TestReadings::Temperature, 1.0);
+        inner.start_record(0);
+        inner.add(TestReadings::Pressure, 2.0);
+    };
+
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2);
+    record(&recorder);
+    let mut csv = Vec::new();
+    recorder
+        .export_csv(
+            &mut csv,
+            RRCsvOptions {
+                group_column: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "index_type,group,record_id,Temperature,Pressure\n\
+         Step,,0,,2\n\
+         Step,outer,0,1,\n"
+    );
+
+    record(&recorder);
+    let mut csv = Vec::new();
+    let rows = recorder
+        .export_csv(
+            &mut csv,
+            RRCsvOptions {
+                only_group: Some("outer".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(rows, 1);
+    let left = recorder.history_view();
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].group, None);
+    recorder.convenient_pop();
+
+    record(&recorder);
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+    let mut groups: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .named_events()
+        .map(|e| e.unwrap().group)
+        .collect();
+    groups.sort();
+    assert_eq!(groups, vec![None, Some("outer".into())]);
+}
+
+#[test]
+fn test_csv_quotes_group_names() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default().name("a, \"b\""));
+        group.start_record(0);
+        group.add(TestReadings::Temperature, 1.0);
+    }
+    let mut csv = Vec::new();
+    recorder
+        .export_csv(
+            &mut csv,
+            RRCsvOptions {
+                group_column: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "index_type,group,record_id,Temperature,Pressure\n\
+         Step,\"a, \"\"b\"\"\",0,1,\n"
+    );
+}
+
+#[test]
+fn test_index_path_roundtrip() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2);
+    {
+        let mut run = recorder.add_group(
+            RapidRecorderGroup::new()
+                .sample_rate(DefaultSamplingFrequency::EveryOne)
+                .index_type(DefaultIndexDimmension::Run),
+        );
+        let mut step = run.add_child_group(RapidRecorderGroup::default());
+        run.start_record(3);
+        step.start_record(17);
+        step.add(TestReadings::Temperature, 1.0);
+    }
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+
+    let events: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let step = events
+        .iter()
+        .find(|e| e.iteration_index == DefaultIndexDimmension::Step)
+        .unwrap();
+    assert_eq!(
+        step.path,
+        vec![
+            (DefaultIndexDimmension::Run, 3),
+            (DefaultIndexDimmension::Step, 17)
+        ]
+    );
+
+    let named: Vec<_> = RecordingReader::new(Cursor::new(&bytes))
+        .unwrap()
+        .named_events()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let step = named.iter().find(|e| e.iteration_index == "Step").unwrap();
+    assert_eq!(
+        step.path,
+        vec![("Run".to_string(), 3), ("Step".to_string(), 17)]
+    );
+}
+
+#[test]
+fn test_malformed_recording_is_invalid_data() {
+    let header = RecordingWriter::new::<DefaultIndexDimmension, TestReadings>(Vec::new())
+        .unwrap()
+        .finish()
+        .unwrap();
+    let read_first = |event: &[u8]| {
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(event);
+        RecordingReader::new(Cursor::new(bytes))
+            .unwrap()
+            .next_raw()
+            .err()
+            .map(|err| err.kind())
+    };
+
+    // an event claiming u32::MAX changed readings in a 16 byte payload
+    let mut huge_count = vec![1u8];
+    huge_count.extend_from_slice(&16u32.to_le_bytes());
+    huge_count.extend_from_slice(&0u32.to_le_bytes());
+    huge_count.extend_from_slice(&0u64.to_le_bytes());
+    huge_count.extend_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        read_first(&huge_count),
+        Some(std::io::ErrorKind::InvalidData)
+    );
+
+    // a payload length far beyond the end of the file
+    let mut huge_payload = vec![1u8];
+    huge_payload.extend_from_slice(&u32::MAX.to_le_bytes());
+    huge_payload.extend_from_slice(&[0; 8]);
+    assert_eq!(
+        read_first(&huge_payload),
+        Some(std::io::ErrorKind::InvalidData)
+    );
+
+    // a reading index that is not a variant of the reading enum
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(10, 2);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(0);
+        group.add(TestReadings::Temperature, 1.0);
+    }
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+    // tag, payload length, id_type, record_id and changed count come before the first reading index
+    let index_at = header.len() + 1 + 4 + 4 + 8 + 4;
+    bytes[index_at..index_at + 4].copy_from_slice(&200u32.to_le_bytes());
+    let err = RecordingReader::new(Cursor::new(bytes))
+        .unwrap()
+        .events::<TestReadings, DefaultIndexDimmension>()
+        .unwrap()
+        .next()
+        .unwrap()
+        .err()
+        .map(|err| err.kind());
+    assert_eq!(err, Some(std::io::ErrorKind::InvalidData));
+}
+
+#[test]
+fn test_save_recording_while_viewing() {
+    let recorder: RapidRecorder<DefaultIndexDimmension, TestReadings> = RapidRecorder::new(500, 2);
+    let done = std::sync::atomic::AtomicBool::new(false);
+    let written = std::thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                recorder.history_view();
+            }
+        });
+        let written: Vec<usize> = (0..20)
+            .map(|_| {
+                for id in 0..500 {
+                    recorder._save_event(0, id);
+                }
+                recorder.save_recording(Vec::new()).unwrap()
+            })
+            .collect();
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        written
+    });
+    // a view emptying the buffer must not look like the end of the history
+    assert_eq!(written, vec![500; 20]);
+}
+
+#[test]
+fn test_newer_versions_may_append_fields() {
+    let recorder: RapidRecorder<_, TestReadings> = RapidRecorder::new(100, 2);
+    {
+        let mut group = recorder.add_group(RapidRecorderGroup::default());
+        group.start_record(7);
+        group.add(TestReadings::Temperature, 21.5);
+    }
+    let mut bytes = Vec::new();
+    recorder.save_recording(&mut bytes).unwrap();
+    // grow the only event, which is right after the header and ends the file, by a field this build doesn't know
+    let event_at = RecordingWriter::new::<DefaultIndexDimmension, TestReadings>(Vec::new())
+        .unwrap()
+        .finish()
+        .unwrap()
+        .len();
+    let len_at = event_at + 1;
+    let len = u32::from_le_bytes(bytes[len_at..len_at + 4].try_into().unwrap());
+    // ids, one 13 byte reading, empty histogram and moment counts, timestamps, no group and no parents
+    assert_eq!(len, 16 + 13 + 8 + 17 + 1 + 4);
+    bytes[len_at..len_at + 4].copy_from_slice(&(len + 3).to_le_bytes());
+    bytes.extend_from_slice(&[1, 2, 3]);
+
+    // in a recording of this version the extra bytes can only be corruption
+    let mut reader = RecordingReader::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(
+        reader.next_raw().err().map(|err| err.kind()),
+        Some(std::io::ErrorKind::InvalidData)
+    );
+
+    let newer = RECORDING_FORMAT_VERSION + 1;
+    bytes[8..10].copy_from_slice(&newer.to_le_bytes());
+    let mut reader = RecordingReader::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(reader.version(), newer);
+    let event = reader.next_raw().unwrap().unwrap();
+    assert_eq!(event.record_id, 7);
+    assert!(reader.next_raw().unwrap().is_none());
+}
@@ -5,7 +5,7 @@ use strum_macros::EnumIter;
 
 // Test enum with standard sequential variants
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 
 enum BasicEnum {
     Variant0,
@@ -16,7 +16,7 @@ impl_rapid_recorder_named_usize!(BasicEnum);
 
 // Test enum with explicit discriminants
 #[repr(u32)]
-#[derive(EnumIter)]
+#[derive(EnumIter, Debug)]
 
 enum ExplicitEnum {
     VariantA = 5,
@@ -76,6 +76,7 @@ fn test_from_usize_conversion() {
 }
 
 #[test]
+#[allow(clippy::clone_on_copy)]
 fn test_clone_impl() {
     let original1 = BasicEnum::Variant1;
     let cloned1 = original1.clone();